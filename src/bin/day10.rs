@@ -1,14 +1,22 @@
 use advent_of_code_2024::grid::{Grid, Position};
 use std::collections::HashSet;
-use std::num::ParseIntError;
 use std::str::FromStr;
 
 const INPUT: &str = "./input/day10.txt";
 
 type Height = u32;
 
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+struct Trails {
+    // Number of distinct hiking trails from this position to any summit.
+    rating: usize,
+    // Summits reachable from this position.
+    summits: HashSet<Position>,
+}
+
+// Impassable tiles have no height.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
-struct Map(Grid<Height>);
+struct Map(Grid<Option<Height>>);
 
 impl Map {
     const TRAILHEAD: Height = 0;
@@ -16,71 +24,64 @@ impl Map {
     const SUMMIT: Height = 9;
 
     fn trailheads(&self) -> impl Iterator<Item = Position> + '_ {
-        (0..self.0.height()).flat_map(move |row| {
-            (0..self.0.width()).filter_map(move |col| {
-                Some(Position::new(row, col)).filter(|p| self.0.get(*p) == Some(&Self::TRAILHEAD))
-            })
-        })
+        self.0
+            .iter()
+            .filter_map(|(p, &h)| Some(p).filter(|_| h == Some(Self::TRAILHEAD)))
     }
 
-    fn trailhead_score(&self, position: Position) -> Option<usize> {
-        if self.0.get(position) != Some(&Self::TRAILHEAD) {
-            return None;
+    // Going down from the summits, every position's trails are those of its neighbours one
+    // step higher.
+    fn trails(&self) -> Grid<Trails> {
+        let mut by_height = vec![Vec::new(); Self::SUMMIT as usize + 1];
+        for (position, &height) in self.0.iter() {
+            if let Some(height) = height.filter(|&h| h <= Self::SUMMIT) {
+                by_height[height as usize].push(position);
+            }
         }
 
-        let mut to_visit = vec![position];
-        let mut visited = HashSet::new();
-        let mut heights = HashSet::new();
-
-        while let Some(position) = to_visit.pop() {
-            if visited.insert(position) {
-                match self.0.get(position) {
-                    Some(&Self::SUMMIT) => {
-                        heights.insert(position);
+        let index = |p: Position| p.x() + p.y() * self.0.width();
+        let mut trails = vec![Trails::default(); self.0.height() * self.0.width()];
+        for (height, positions) in by_height.into_iter().enumerate().rev() {
+            for position in positions {
+                trails[index(position)] = if height as Height == Self::SUMMIT {
+                    Trails {
+                        rating: 1,
+                        summits: HashSet::from([position]),
                     }
-                    Some(current_height) => {
-                        for neighbor in position.neighbours() {
-                            if self.0.get(neighbor) == Some(&(current_height + Self::STEP)) {
-                                to_visit.push(neighbor);
-                            }
-                        }
-                    }
-                    None => (),
-                }
+                } else {
+                    position
+                        .neighbours()
+                        .filter(|&n| self.0.get(n) == Some(&Some(height as Height + Self::STEP)))
+                        .fold(Trails::default(), |mut acc, n| {
+                            let next = &trails[index(n)];
+                            acc.rating += next.rating;
+                            acc.summits.extend(&next.summits);
+                            acc
+                        })
+                };
             }
         }
 
-        Some(heights.len())
+        Grid::new(self.0.height(), self.0.width(), trails).unwrap()
     }
 
-    fn trailhead_rating(&self, position: Position) -> Option<usize> {
-        if self.0.get(position) != Some(&Self::TRAILHEAD) {
-            return None;
-        }
-
-        let mut to_visit = vec![position];
-        let mut count = 0;
-
-        while let Some(position) = to_visit.pop() {
-            match self.0.get(position) {
-                Some(&Self::SUMMIT) => count += 1,
-                Some(current_height) => {
-                    for neighbor in position.neighbours() {
-                        if self.0.get(neighbor) == Some(&(current_height + Self::STEP)) {
-                            to_visit.push(neighbor);
-                        }
-                    }
-                }
-                None => (),
-            }
-        }
+    fn total_score(&self) -> usize {
+        let trails = self.trails();
+        self.trailheads()
+            .map(|p| trails.get(p).unwrap().summits.len())
+            .sum()
+    }
 
-        Some(count)
+    fn total_rating(&self) -> usize {
+        let trails = self.trails();
+        self.trailheads()
+            .map(|p| trails.get(p).unwrap().rating)
+            .sum()
     }
 }
 
 impl FromStr for Map {
-    type Err = ParseIntError;
+    type Err = char;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let height = s.lines().count();
@@ -88,8 +89,12 @@ impl FromStr for Map {
 
         let grid = s
             .lines()
-            .flat_map(|line| line.chars().map(|c| c.to_digit(10).unwrap()))
-            .collect();
+            .flat_map(str::chars)
+            .map(|c| match c {
+                '.' => Ok(None),
+                _ => c.to_digit(10).map(Some).ok_or(c),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
 
         Ok(Self(Grid::new(height, width, grid).unwrap()))
     }
@@ -97,20 +102,12 @@ impl FromStr for Map {
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let input = std::fs::read_to_string(INPUT)?;
-    let map = input.parse::<Map>()?;
-
-    println!(
-        "The first answer is: {}",
-        map.trailheads()
-            .filter_map(|p| map.trailhead_score(p))
-            .sum::<usize>()
-    );
-    println!(
-        "The second answer is: {}",
-        map.trailheads()
-            .filter_map(|p| map.trailhead_rating(p))
-            .sum::<usize>()
-    );
+    let map = input
+        .parse::<Map>()
+        .map_err(|c| format!("Invalid character: {c}"))?;
+
+    println!("The first answer is: {}", map.total_score());
+    println!("The second answer is: {}", map.total_rating());
 
     Ok(())
 }
@@ -139,10 +136,7 @@ mod test {
     #[test]
     fn test_part1_ex1() {
         let map = EXAMPLE1.parse::<Map>().unwrap();
-        let actual = map
-            .trailheads()
-            .filter_map(|p| map.trailhead_score(p))
-            .sum();
+        let actual = map.total_score();
         let expected = 1_usize;
         assert_eq!(expected, actual);
     }
@@ -150,10 +144,7 @@ mod test {
     #[test]
     fn test_part1_ex2() {
         let map = EXAMPLE2.parse::<Map>().unwrap();
-        let actual = map
-            .trailheads()
-            .filter_map(|p| map.trailhead_score(p))
-            .sum();
+        let actual = map.total_score();
         let expected = 36_usize;
         assert_eq!(expected, actual);
     }
@@ -161,11 +152,41 @@ mod test {
     #[test]
     fn test_part_ex2() {
         let map = EXAMPLE2.parse::<Map>().unwrap();
-        let actual = map
-            .trailheads()
-            .filter_map(|p| map.trailhead_rating(p))
-            .sum();
+        let actual = map.total_rating();
         let expected = 81_usize;
         assert_eq!(expected, actual);
     }
+
+    #[test]
+    fn impassable_tiles() {
+        let examples = [
+            (
+                "...0...\n...1...\n...2...\n6543456\n7.....7\n8.....8\n9.....9\n",
+                2,
+                2,
+            ),
+            (
+                "..90..9\n...1.98\n...2..7\n6543456\n765.987\n876....\n987....\n",
+                4,
+                13,
+            ),
+            (
+                "10..9..\n2...8..\n3...7..\n4567654\n...8..3\n...9..2\n.....01\n",
+                3,
+                3,
+            ),
+            (
+                ".....0.\n..4321.\n..5..2.\n..6543.\n..7..4.\n..8765.\n..9....\n",
+                1,
+                3,
+            ),
+        ];
+
+        for (example, score, rating) in examples {
+            let map = example.parse::<Map>().unwrap();
+
+            assert_eq!(score, map.total_score());
+            assert_eq!(rating, map.total_rating());
+        }
+    }
 }
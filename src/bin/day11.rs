@@ -1,10 +1,22 @@
 use radixal::IntoDigits;
 use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
 use std::num::ParseIntError;
 use std::str::FromStr;
 
 const INPUT: &str = "./input/day11.txt";
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct Overflow;
+
+impl Display for Overflow {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Arithmetic overflow while evolving the stones.")
+    }
+}
+
+impl std::error::Error for Overflow {}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 struct Stone(u64);
 
@@ -13,24 +25,29 @@ impl Stone {
         Self(number)
     }
 
-    fn evolve(self) -> Box<dyn Iterator<Item = Self>> {
+    fn blink(self) -> Result<(Self, Option<Self>), Overflow> {
         if self.0 == 0 {
-            Box::new(std::iter::once(Self::new(1)))
+            Ok((Self::new(1), None))
         } else {
             let mut iter = self.0.into_decimal_digits();
-            if iter.len() % 2 == 0 {
+            if iter.len().is_multiple_of(2) {
                 let l = iter.len();
                 let left = iter.by_ref().take(l / 2).fold(0, |acc, d| acc * 10 + d);
 
-                Box::new(
-                    std::iter::once(Self::new(left))
-                        .chain(std::iter::once(Self::new(iter.into_number()))),
-                )
+                Ok((Self::new(left), Some(Self::new(iter.into_number()))))
             } else {
-                Box::new(std::iter::once(Self::new(self.0 * 2024)))
+                self.0
+                    .checked_mul(2024)
+                    .map(|n| (Self::new(n), None))
+                    .ok_or(Overflow)
             }
         }
     }
+
+    fn evolve(self) -> impl Iterator<Item = Self> {
+        let (left, right) = self.blink().expect("Stone numbers should fit in 64 bits.");
+        std::iter::once(left).chain(right)
+    }
 }
 
 impl FromStr for Stone {
@@ -73,6 +90,76 @@ fn count_evolve(stones: &[Stone], generations: usize) -> usize {
         .sum()
 }
 
+// The order of the stones never matters, only how many of each there are. Once a count no
+// longer fits, the counts saturate and are marked as overflowed, so that the distinct numbers
+// can still be followed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct StoneCounts {
+    counts: HashMap<Stone, u128>,
+    overflowed: bool,
+}
+
+impl StoneCounts {
+    fn new(stones: &[Stone]) -> Self {
+        let mut counts = HashMap::new();
+        for &stone in stones {
+            *counts.entry(stone).or_insert(0) += 1;
+        }
+
+        Self {
+            counts,
+            overflowed: false,
+        }
+    }
+
+    fn blink(&self) -> Result<Self, Overflow> {
+        let mut counts = HashMap::with_capacity(self.counts.len());
+        let mut overflowed = self.overflowed;
+        for (&stone, &count) in &self.counts {
+            let (left, right) = stone.blink()?;
+            for stone in std::iter::once(left).chain(right) {
+                let entry = counts.entry(stone).or_insert(0_u128);
+                *entry = entry.checked_add(count).unwrap_or_else(|| {
+                    overflowed = true;
+                    u128::MAX
+                });
+            }
+        }
+
+        Ok(Self { counts, overflowed })
+    }
+
+    fn total(&self) -> Result<u128, Overflow> {
+        if self.overflowed {
+            return Err(Overflow);
+        }
+
+        self.counts
+            .values()
+            .try_fold(0_u128, |acc, &count| acc.checked_add(count))
+            .ok_or(Overflow)
+    }
+
+    fn distinct(&self) -> usize {
+        self.counts.len()
+    }
+}
+
+// Every generation, starting with the initial stones, stopping if a stone number overflows.
+fn evolutions(stones: &[Stone]) -> impl Iterator<Item = Result<StoneCounts, Overflow>> {
+    std::iter::successors(Some(Ok(StoneCounts::new(stones))), |counts| {
+        counts.as_ref().ok().map(StoneCounts::blink)
+    })
+}
+
+fn count_blinks(stones: &[Stone], generations: usize) -> Result<u128, Overflow> {
+    evolutions(stones)
+        .take(generations + 1)
+        .last()
+        .expect("There is always at least the initial generation.")?
+        .total()
+}
+
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let input = std::fs::read_to_string(INPUT)?;
     let stones = input
@@ -80,6 +167,21 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         .map(Stone::from_str)
         .collect::<Result<Vec<_>, _>>()?;
 
+    // Usage: day11 [BLINKS]
+    if let Some(blinks) = std::env::args().nth(1) {
+        let blinks = blinks.parse()?;
+        for (generation, counts) in evolutions(&stones).take(blinks + 1).enumerate() {
+            println!(
+                "After {generation} blinks: {} distinct numbers.",
+                counts?.distinct()
+            );
+        }
+        match count_blinks(&stones, blinks) {
+            Ok(total) => println!("After {blinks} blinks: {total} stones."),
+            Err(Overflow) => println!("After {blinks} blinks: too many stones to count."),
+        }
+    }
+
     println!("The first answer is: {}", count_evolve(&stones, 25));
     println!("The second answer is: {}", count_evolve(&stones, 75));
 
@@ -158,4 +260,55 @@ mod test {
         let expected = 55312;
         assert_eq!(expected, actual);
     }
+
+    #[test]
+    fn counted_evolution() {
+        let stones: Vec<Stone> = EXAMPLE
+            .split_whitespace()
+            .map(Stone::from_str)
+            .collect::<Result<Vec<Stone>, _>>()
+            .unwrap();
+
+        for generations in 0..=40 {
+            let expected = count_evolve(&stones, generations) as u128;
+            assert_eq!(Ok(expected), count_blinks(&stones, generations));
+        }
+    }
+
+    #[test]
+    fn counted_distribution() {
+        let stones: Vec<Stone> = EXAMPLE
+            .split_whitespace()
+            .map(Stone::from_str)
+            .collect::<Result<Vec<Stone>, _>>()
+            .unwrap();
+        let actual = evolutions(&stones).nth(6).unwrap().unwrap();
+        let expected = StoneCounts::new(
+            &[
+                2097446912, 14168, 4048, 2, 0, 2, 4, 40, 48, 2024, 40, 48, 80, 96, 2, 8, 6, 7, 6,
+                0, 3, 2,
+            ]
+            .map(Stone::new),
+        );
+
+        assert_eq!(expected, actual);
+        assert_eq!(15, actual.distinct());
+        assert_eq!(Some(&4), actual.counts.get(&Stone(2)));
+    }
+
+    #[test]
+    fn overflow() {
+        assert_eq!(Err(Overflow), Stone::new(1_000_000_000_000_000_000).blink());
+        assert_eq!(Err(Overflow), count_blinks(&[Stone::new(125)], 1000));
+    }
+
+    #[test]
+    fn distribution_past_overflow() {
+        let mut generations = evolutions(&[Stone::new(125)]);
+        let counts = generations.nth(1000).unwrap().unwrap();
+
+        assert!(counts.overflowed);
+        assert_eq!(Err(Overflow), counts.total());
+        assert_eq!(54, counts.distinct());
+    }
 }
@@ -1,64 +1,35 @@
-use advent_of_code_2024::grid::{Direction, Grid, Position};
+use advent_of_code_2024::grid::{Connectivity, Direction, Grid, Position};
 use std::collections::{BTreeSet, HashSet};
 use std::convert::Infallible;
 use std::str::FromStr;
 
 const INPUT: &str = "./input/day12.txt";
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
-struct Farm(Grid<char>);
-
-impl Farm {
-    fn plots(&self) -> Vec<HashSet<Position>> {
-        let mut plots = Vec::new();
-        let mut positions: BTreeSet<Position> = (0..self.0.width())
-            .flat_map(move |idx| ((0..self.0.height()).map(move |idy| Position::new(idx, idy))))
-            .collect();
-
-        while let Some(position) = positions.pop_first() {
-            let plant = self.0.get(position).unwrap();
-
-            let mut plot = HashSet::new();
-            let mut to_visit = vec![position];
-
-            while let Some(position) = to_visit.pop() {
-                if plot.insert(position) {
-                    positions.remove(&position);
-                    for neighbour in position.neighbours() {
-                        if self.0.get(neighbour) == Some(plant) {
-                            to_visit.push(neighbour);
-                        }
-                    }
-                }
-            }
-
-            plots.push(plot);
-        }
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Region {
+    plant: char,
+    positions: HashSet<Position>,
+}
 
-        plots
+impl Region {
+    fn area(&self) -> usize {
+        self.positions.len()
     }
 
-    fn fencing_cost(&self) -> usize {
-        self.plots()
+    fn perimeter(&self) -> usize {
+        self.positions
             .iter()
-            .map(|plot| {
-                let perimeter: usize = plot
-                    .iter()
-                    .map(|position| {
-                        // Start with four sides, but remove any that are bordered by a similar
-                        // plant.
-                        4 - position
-                            .neighbours()
-                            .filter(|neighbour| plot.contains(neighbour))
-                            .count()
-                    })
-                    .sum();
-                perimeter * plot.len()
+            .map(|position| {
+                // Start with four sides, but remove any that are bordered by a similar plant.
+                4 - position
+                    .neighbours()
+                    .filter(|neighbour| self.positions.contains(neighbour))
+                    .count()
             })
             .sum()
     }
 
-    fn discount_fencing_cost(&self) -> usize {
+    fn sides(&self) -> usize {
         const DIRECTIONS: [Direction; 4] = [
             Direction::Up,
             Direction::Down,
@@ -66,41 +37,127 @@ impl Farm {
             Direction::Right,
         ];
 
-        self.plots()
+        // Find the boundaries of our polygon (including interior boundaries), as pairs of
+        // position and direction.
+        let mut boundaries: BTreeSet<(Position, Direction)> = self
+            .positions
             .iter()
-            .map(|plot| {
-                // Find the boundaries of our polygon (including interior boundaries), as pairs of
-                // position and direction.
-                let mut boundaries: BTreeSet<(Position, Direction)> = plot
-                    .iter()
-                    .flat_map(|&position| DIRECTIONS.iter().map(move |&d| (position, d)))
-                    .filter(|&(p, d)| (p + d).map_or(true, |pd| !plot.contains(&pd)))
-                    .collect();
-
-                let mut sides = 0;
-                // Start with any boundary, then follow that boundary left and right to form a side.
-                // Remove those boundaries (position/direction pair) that this side accounts for.
-                while let Some(pd) = boundaries.pop_first() {
-                    sides += 1;
-                    let mut to_visit = vec![pd];
-                    while let Some((position, direction)) = to_visit.pop() {
-                        if let Some(p) = position + direction.rotate_left() {
-                            let left = (p, direction);
-                            if boundaries.remove(&left) {
-                                to_visit.push(left);
-                            }
-                        }
-                        if let Some(p) = position + direction.rotate_right() {
-                            let right = (p, direction);
-                            if boundaries.remove(&right) {
-                                to_visit.push(right);
-                            }
-                        }
+            .flat_map(|&position| DIRECTIONS.iter().map(move |&d| (position, d)))
+            .filter(|&(p, d)| (p + d).is_none_or(|pd| !self.positions.contains(&pd)))
+            .collect();
+
+        let mut sides = 0;
+        // Start with any boundary, then follow that boundary left and right to form a side.
+        // Remove those boundaries (position/direction pair) that this side accounts for.
+        while let Some(pd) = boundaries.pop_first() {
+            sides += 1;
+            let mut to_visit = vec![pd];
+            while let Some((position, direction)) = to_visit.pop() {
+                if let Some(p) = position + direction.rotate_left() {
+                    let left = (p, direction);
+                    if boundaries.remove(&left) {
+                        to_visit.push(left);
                     }
                 }
+                if let Some(p) = position + direction.rotate_right() {
+                    let right = (p, direction);
+                    if boundaries.remove(&right) {
+                        to_visit.push(right);
+                    }
+                }
+            }
+        }
+
+        sides
+    }
+
+    // Top-left and bottom-right corners (inclusive).
+    fn bounding_box(&self) -> (Position, Position) {
+        let min_x = self
+            .positions
+            .iter()
+            .map(|p| p.x())
+            .min()
+            .unwrap_or_default();
+        let max_x = self
+            .positions
+            .iter()
+            .map(|p| p.x())
+            .max()
+            .unwrap_or_default();
+        let min_y = self
+            .positions
+            .iter()
+            .map(|p| p.y())
+            .min()
+            .unwrap_or_default();
+        let max_y = self
+            .positions
+            .iter()
+            .map(|p| p.y())
+            .max()
+            .unwrap_or_default();
+
+        (Position::new(min_x, min_y), Position::new(max_x, max_y))
+    }
+
+    // Number of separate areas fully surrounded by this region (which must then hold other
+    // regions).
+    fn holes(&self) -> usize {
+        // Work within the bounding box, with a one tile margin all around (shifting everything by
+        // one), so that the outside is a single connected area.
+        let (top_left, bottom_right) = self.bounding_box();
+        let width = bottom_right.x() - top_left.x() + 3;
+        let height = bottom_right.y() - top_left.y() + 3;
+        let tiles = (0..height)
+            .flat_map(|idy| (0..width).map(move |idx| (idx, idy)))
+            .map(|(idx, idy)| {
+                self.positions.contains(&Position::new(
+                    (idx + top_left.x()).wrapping_sub(1),
+                    (idy + top_left.y()).wrapping_sub(1),
+                ))
+            })
+            .collect();
+        let (_, components) = Grid::new(height, width, tiles)
+            .unwrap()
+            .components(Connectivity::Four);
+
+        // Apart from the region itself, there is the outside, then any holes.
+        components.len() - 2
+    }
+
+    fn encloses_other_regions(&self) -> bool {
+        self.holes() > 0
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Farm(Grid<char>);
 
-                sides * plot.len()
+impl Farm {
+    fn regions(&self) -> Vec<Region> {
+        let (_, components) = self.0.components(Connectivity::Four);
+
+        components
+            .into_iter()
+            .map(|positions| Region {
+                plant: *self.0.get(positions[0]).unwrap(),
+                positions: positions.into_iter().collect(),
             })
+            .collect()
+    }
+
+    fn fencing_cost(&self) -> usize {
+        self.regions()
+            .iter()
+            .map(|region| region.area() * region.perimeter())
+            .sum()
+    }
+
+    fn discount_fencing_cost(&self) -> usize {
+        self.regions()
+            .iter()
+            .map(|region| region.area() * region.sides())
             .sum()
     }
 }
@@ -121,6 +178,30 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let input = std::fs::read_to_string(INPUT)?;
     let farm = input.parse::<Farm>()?;
 
+    // Usage: day12 [--breakdown]
+    if std::env::args().skip(1).any(|arg| arg == "--breakdown") {
+        for region in farm.regions() {
+            let (top_left, bottom_right) = region.bounding_box();
+            println!(
+                "A region of {} plants from {} to {} with price {} * {} = {} (or {} * {} = {} with the discount){}.",
+                region.plant,
+                top_left,
+                bottom_right,
+                region.area(),
+                region.perimeter(),
+                region.area() * region.perimeter(),
+                region.area(),
+                region.sides(),
+                region.area() * region.sides(),
+                if region.encloses_other_regions() {
+                    format!(", enclosing {} other area(s)", region.holes())
+                } else {
+                    String::new()
+                }
+            );
+        }
+    }
+
     println!("The first answer is: {}", farm.fencing_cost());
     println!("The second answer is: {}", farm.discount_fencing_cost());
 
@@ -157,6 +238,61 @@ mod test {
         MMMISSJEEE\n\
     ";
 
+    #[test]
+    fn regions_example1() {
+        let farm = EXAMPLE_1.parse::<Farm>().unwrap();
+
+        let actual: Vec<(char, usize, usize, usize)> = farm
+            .regions()
+            .iter()
+            .map(|r| (r.plant, r.area(), r.perimeter(), r.sides()))
+            .collect();
+        let expected = vec![
+            ('A', 4, 10, 4),
+            ('B', 4, 8, 4),
+            ('C', 4, 10, 8),
+            ('D', 1, 4, 4),
+            ('E', 3, 8, 4),
+        ];
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn regions_bounding_box() {
+        let farm = EXAMPLE_1.parse::<Farm>().unwrap();
+
+        let actual: Vec<(Position, Position)> =
+            farm.regions().iter().map(Region::bounding_box).collect();
+        let expected = vec![
+            (Position::new(0, 0), Position::new(3, 0)),
+            (Position::new(0, 1), Position::new(1, 2)),
+            (Position::new(2, 1), Position::new(3, 3)),
+            (Position::new(3, 1), Position::new(3, 1)),
+            (Position::new(0, 3), Position::new(2, 3)),
+        ];
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn regions_holes() {
+        let farm = EXAMPLE_2.parse::<Farm>().unwrap();
+        let regions = farm.regions();
+
+        assert_eq!(4, regions[0].holes());
+        assert!(regions[0].encloses_other_regions());
+        assert!(regions[1..].iter().all(|r| !r.encloses_other_regions()));
+
+        let farm = "AAAAAA\nAAABBA\nAAABBA\nABBAAA\nABBAAA\nAAAAAA\n"
+            .parse::<Farm>()
+            .unwrap();
+        let actual: Vec<usize> = farm.regions().iter().map(Region::holes).collect();
+        let expected = vec![2, 0, 0];
+
+        assert_eq!(expected, actual);
+    }
+
     #[test]
     fn part1_example1() {
         let farm = EXAMPLE_1.parse::<Farm>().unwrap();
@@ -14,26 +14,68 @@ impl Coordinates {
     fn new(x: i64, y: i64) -> Self {
         Self { x, y }
     }
+
+    fn checked_add(self, other: Self) -> Result<Self, Overflow> {
+        Ok(Self {
+            x: self.x.checked_add(other.x).ok_or(Overflow)?,
+            y: self.y.checked_add(other.y).ok_or(Overflow)?,
+        })
+    }
+
+    fn checked_mul(self, other: i64) -> Result<Self, Overflow> {
+        Ok(Self {
+            x: self.x.checked_mul(other).ok_or(Overflow)?,
+            y: self.y.checked_mul(other).ok_or(Overflow)?,
+        })
+    }
+
+    // Z component of the cross product.
+    fn cross(self, other: Self) -> Result<i64, Overflow> {
+        self.x
+            .checked_mul(other.y)
+            .zip(self.y.checked_mul(other.x))
+            .and_then(|(l, r)| l.checked_sub(r))
+            .ok_or(Overflow)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct Overflow;
+
+impl std::fmt::Display for Overflow {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Arithmetic overflow while solving a claw machine.")
+    }
 }
 
-impl std::ops::Add for Coordinates {
-    type Output = Self;
+impl std::error::Error for Overflow {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct ClawRules {
+    cost_a: i64,
+    cost_b: i64,
+    press_limit: Option<i64>,
+    // Added to both coordinates of every prize.
+    prize_offset: i64,
+}
 
-    fn add(self, other: Self) -> Self {
+impl Default for ClawRules {
+    fn default() -> Self {
         Self {
-            x: self.x + other.x,
-            y: self.y + other.y,
+            cost_a: 3,
+            cost_b: 1,
+            press_limit: Some(100),
+            prize_offset: 0,
         }
     }
 }
 
-impl std::ops::Mul<i64> for Coordinates {
-    type Output = Self;
-
-    fn mul(self, other: i64) -> Self {
+impl ClawRules {
+    fn corrected() -> Self {
         Self {
-            x: self.x * other,
-            y: self.y * other,
+            press_limit: None,
+            prize_offset: 10000000000000,
+            ..Self::default()
         }
     }
 }
@@ -46,46 +88,144 @@ struct Machine {
 }
 
 impl Machine {
-    const COST_A: i64 = 3;
-    const COST_B: i64 = 1;
-    const CORRECTION: i64 = 10000000000000;
-
-    fn minimal_cost(&self) -> Option<i64> {
-        // We can use Cramer's rule to solve the linear system of equations.
-        // Note: this only works because our inputs are not collinear.
-        let determinant = self.a.x * self.b.y - self.a.y * self.b.x;
-        let a = (self.prize.x * self.b.y - self.prize.y * self.b.x) / determinant;
-        let b = (self.prize.y * self.a.x - self.prize.x * self.a.y) / determinant;
+    // Cheapest number of presses of both buttons to reach the prize, if possible.
+    fn presses(&self, rules: &ClawRules) -> Result<Option<(i64, i64)>, Overflow> {
+        let prize = self
+            .prize
+            .checked_add(Coordinates::new(rules.prize_offset, rules.prize_offset))?;
+        let determinant = self.a.cross(self.b)?;
+        let presses = if determinant != 0 {
+            // We can use Cramer's rule to solve the linear system of equations.
+            let a = prize.cross(self.b)? / determinant;
+            let b = self.a.cross(prize)? / determinant;
+            Some((a, b))
+        } else {
+            self.collinear_presses(prize, rules)?
+        };
 
         // Since our division above ignores remainders, we have to check that we are indeed falling
-        // on our correction prize.
-        if a <= 100 && b <= 100 && self.a * a + self.b * b == self.prize {
-            Some(a * Self::COST_A + b * Self::COST_B)
+        // on our prize (this also rules out a prize off the line of collinear buttons).
+        match presses {
+            Some((a, b))
+                if a >= 0
+                    && b >= 0
+                    && rules
+                        .press_limit
+                        .is_none_or(|limit| a <= limit && b <= limit)
+                    && self.a.checked_mul(a)?.checked_add(self.b.checked_mul(b)?)? == prize =>
+            {
+                Ok(Some((a, b)))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    // With collinear buttons, there can be many solutions (or none), which all lie on a line.
+    fn collinear_presses(
+        &self,
+        prize: Coordinates,
+        rules: &ClawRules,
+    ) -> Result<Option<(i64, i64)>, Overflow> {
+        // Work along whichever axis the buttons move on.
+        let (a, b, p) = if self.a.x != 0 || self.b.x != 0 {
+            (self.a.x, self.b.x, prize.x)
         } else {
-            None
+            (self.a.y, self.b.y, prize.y)
+        };
+        if a == 0 && b == 0 {
+            return Ok(Some((0, 0)));
+        }
+
+        let (gcd, x, y) = extended_gcd(a, b);
+        if p % gcd != 0 {
+            return Ok(None);
+        }
+
+        // Every solution is (a0 + k * da, b0 + k * db), and we need both to be within bounds.
+        let a0 = x.checked_mul(p / gcd).ok_or(Overflow)?;
+        let b0 = y.checked_mul(p / gcd).ok_or(Overflow)?;
+        let (da, db) = (b / gcd, -a / gcd);
+        let (Some((low_a, high_a)), Some((low_b, high_b))) = (
+            press_range(a0, da, rules.press_limit)?,
+            press_range(b0, db, rules.press_limit)?,
+        ) else {
+            return Ok(None);
+        };
+        let (low, high) = (low_a.max(low_b), high_a.min(high_b));
+        if low > high {
+            return Ok(None);
         }
+
+        // The cost is linear along that line, so the cheapest is at either end.
+        let slope = rules
+            .cost_a
+            .checked_mul(da)
+            .zip(rules.cost_b.checked_mul(db))
+            .and_then(|(a, b)| a.checked_add(b))
+            .ok_or(Overflow)?;
+        let k = if slope >= 0 { low } else { high };
+        if k == i64::MIN || k == i64::MAX {
+            return Ok(None);
+        }
+
+        let step = |start: i64, step: i64| {
+            k.checked_mul(step)
+                .and_then(|n| n.checked_add(start))
+                .ok_or(Overflow)
+        };
+        Ok(Some((step(a0, da)?, step(b0, db)?)))
     }
 
-    fn minimal_cost_corrected(&self) -> Option<i64> {
-        let corrected_prize = Coordinates::new(
-            self.prize.x + Self::CORRECTION,
-            self.prize.y + Self::CORRECTION,
-        );
+    fn minimal_cost(&self, rules: &ClawRules) -> Result<Option<i64>, Overflow> {
+        self.presses(rules)?
+            .map(|(a, b)| {
+                a.checked_mul(rules.cost_a)
+                    .zip(b.checked_mul(rules.cost_b))
+                    .and_then(|(a, b)| a.checked_add(b))
+                    .ok_or(Overflow)
+            })
+            .transpose()
+    }
+}
 
-        // We can use Cramer's rule to solve the linear system of equations.
-        // Note: this only works because our inputs are not collinear.
-        let determinant = self.a.x * self.b.y - self.a.y * self.b.x;
-        let a = (corrected_prize.x * self.b.y - corrected_prize.y * self.b.x) / determinant;
-        let b = (corrected_prize.y * self.a.x - corrected_prize.x * self.a.y) / determinant;
+// Returns (gcd, x, y) such that a * x + b * y = gcd.
+fn extended_gcd(a: i64, b: i64) -> (i64, i64, i64) {
+    if b == 0 {
+        (a.abs(), a.signum(), 0)
+    } else {
+        let (gcd, x, y) = extended_gcd(b, a % b);
+        (gcd, y, x - (a / b) * y)
+    }
+}
 
-        // Since our division above ignores remainders, we have to check that we are indeed falling
-        // on our correction prize.
-        if self.a * a + self.b * b == corrected_prize {
-            Some(a * Self::COST_A + b * Self::COST_B)
+// Range of k for which `start + k * step` is a valid number of presses.
+fn press_range(start: i64, step: i64, limit: Option<i64>) -> Result<Option<(i64, i64)>, Overflow> {
+    let floor_div = |n: i64, d: i64| {
+        let q = n / d;
+        if n % d != 0 && (n < 0) != (d < 0) {
+            q - 1
         } else {
-            None
+            q
         }
-    }
+    };
+    let ceil_div = |n: i64, d: i64| -floor_div(-n, d);
+    let to_zero = start.checked_neg().ok_or(Overflow)?;
+    let to_limit = limit
+        .map(|limit| limit.checked_sub(start).ok_or(Overflow))
+        .transpose()?;
+
+    Ok(match step.signum() {
+        0 => Some((i64::MIN, i64::MAX))
+            .filter(|_| start >= 0 && limit.is_none_or(|limit| start <= limit)),
+        1 => Some((
+            ceil_div(to_zero, step),
+            to_limit.map_or(i64::MAX, |n| floor_div(n, step)),
+        )),
+        _ => Some((
+            to_limit.map_or(i64::MIN, |n| ceil_div(n, step)),
+            floor_div(to_zero, step),
+        )),
+    })
 }
 
 impl FromStr for Machine {
@@ -135,19 +275,21 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         .map(str::parse)
         .collect::<Result<Vec<Machine>, _>>()?;
 
-    println!(
-        "The first answer is: {}",
+    let total_cost = |rules: ClawRules| {
         machines
             .iter()
-            .filter_map(Machine::minimal_cost)
-            .sum::<i64>()
-    );
+            .map(|machine| machine.minimal_cost(&rules))
+            .try_fold(0_i64, |acc, cost| {
+                cost?
+                    .map_or(Some(acc), |cost| acc.checked_add(cost))
+                    .ok_or(Overflow)
+            })
+    };
+
+    println!("The first answer is: {}", total_cost(ClawRules::default())?);
     println!(
         "The second answer is: {}",
-        machines
-            .iter()
-            .filter_map(Machine::minimal_cost_corrected)
-            .sum::<i64>()
+        total_cost(ClawRules::corrected())?
     );
 
     Ok(())
@@ -184,7 +326,7 @@ mod test {
             .unwrap();
         let actual: Vec<Option<i64>> = machines
             .into_iter()
-            .map(|machine| machine.minimal_cost())
+            .map(|machine| machine.minimal_cost(&ClawRules::default()).unwrap())
             .collect();
         let expected = vec![Some(280), None, Some(200), None];
 
@@ -200,7 +342,7 @@ mod test {
             .unwrap();
         let actual: Vec<Option<i64>> = machines
             .into_iter()
-            .map(|machine| machine.minimal_cost_corrected())
+            .map(|machine| machine.minimal_cost(&ClawRules::corrected()).unwrap())
             .collect();
 
         assert!(actual[0].is_none());
@@ -208,4 +350,83 @@ mod test {
         assert!(actual[2].is_none());
         assert!(actual[3].is_some());
     }
+
+    fn machine(a: (i64, i64), b: (i64, i64), prize: (i64, i64)) -> Machine {
+        Machine {
+            a: Coordinates::new(a.0, a.1),
+            b: Coordinates::new(b.0, b.1),
+            prize: Coordinates::new(prize.0, prize.1),
+        }
+    }
+
+    #[test]
+    fn collinear_buttons() {
+        let cost = |m: Machine| m.minimal_cost(&ClawRules::default()).unwrap();
+
+        // B is cheaper per distance.
+        assert_eq!(Some(5), cost(machine((1, 1), (2, 2), (10, 10))));
+        assert_eq!(Some(8), cost(machine((1, 1), (2, 2), (11, 11))));
+        // A is cheaper per distance.
+        assert_eq!(Some(6), cost(machine((5, 5), (1, 1), (10, 10))));
+        // The press limit forces using the more expensive button.
+        assert_eq!(Some(400), cost(machine((1, 1), (2, 2), (300, 300))));
+        assert_eq!(None, cost(machine((1, 1), (2, 2), (500, 500))));
+        // Off the line, or not a multiple of the step.
+        assert_eq!(None, cost(machine((1, 1), (2, 2), (3, 4))));
+        assert_eq!(None, cost(machine((3, 3), (6, 6), (10, 10))));
+        // Steps sharing a common factor, where only some combinations land on the prize.
+        assert_eq!(Some(8), cost(machine((6, 9), (4, 6), (26, 39))));
+        assert_eq!(
+            Ok(Some(ClawRules::corrected().prize_offset / 2)),
+            machine((1, 1), (2, 2), (0, 0)).minimal_cost(&ClawRules::corrected())
+        );
+    }
+
+    #[test]
+    fn custom_rules() {
+        let rules = ClawRules {
+            cost_a: 1,
+            cost_b: 3,
+            press_limit: Some(80),
+            prize_offset: 0,
+        };
+
+        // Cheaper presses of A, but a tighter press limit.
+        assert_eq!(
+            Ok(Some(80 + 40 * 3)),
+            machine((1, 0), (0, 1), (80, 40)).minimal_cost(&rules)
+        );
+        assert_eq!(
+            Ok(None),
+            machine((1, 0), (0, 1), (81, 40)).minimal_cost(&rules)
+        );
+        assert_eq!(
+            Ok(Some(12)),
+            machine((1, 1), (2, 2), (12, 12)).minimal_cost(&rules)
+        );
+
+        let rules = ClawRules {
+            prize_offset: 7,
+            ..ClawRules::default()
+        };
+        assert_eq!(
+            Ok(Some(3 + 3)),
+            machine((1, 0), (0, 1), (-6, -4)).minimal_cost(&rules)
+        );
+    }
+
+    #[test]
+    fn overflow() {
+        let rules = ClawRules {
+            prize_offset: i64::MAX,
+            ..ClawRules::corrected()
+        };
+        assert_eq!(
+            Err(Overflow),
+            machine((1, 2), (3, 4), (5, 6)).minimal_cost(&rules)
+        );
+
+        let huge = machine((i64::MAX, 1), (1, i64::MAX), (1, 1));
+        assert_eq!(Err(Overflow), huge.minimal_cost(&ClawRules::default()));
+    }
 }
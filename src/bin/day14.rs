@@ -1,9 +1,10 @@
 use advent_of_code_2024::position::{Direction, Position};
 use counter::Counter;
 use regex::Regex;
-use std::collections::HashSet;
 use std::fmt::{Display, Formatter};
 use std::num::ParseIntError;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
 const INPUT: &str = "./input/day14.txt";
@@ -11,18 +12,15 @@ const INPUT: &str = "./input/day14.txt";
 const WIDTH: isize = 101;
 const HEIGHT: isize = 103;
 
+const USAGE: &str =
+    "Usage: day14 [WIDTH HEIGHT] [--export DIRECTORY] [--frames START..END] [--heat-map]";
+
 #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
 struct Robot {
     position: Position,
     velocity: Direction,
 }
 
-impl Robot {
-    fn new(position: Position, velocity: Direction) -> Self {
-        Self { position, velocity }
-    }
-}
-
 impl FromStr for Robot {
     type Err = ParseIntError;
 
@@ -38,13 +36,41 @@ impl FromStr for Robot {
     }
 }
 
+fn gcd(a: isize, b: isize) -> isize {
+    if b == 0 {
+        a.abs()
+    } else {
+        gcd(b, a % b)
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
-struct RobotGrid<const HEIGHT: isize, const WIDTH: isize>(Vec<Robot>);
+struct RobotGrid {
+    width: isize,
+    height: isize,
+    robots: Vec<Robot>,
+}
+
+impl RobotGrid {
+    fn new(width: isize, height: isize, robots: Vec<Robot>) -> Self {
+        Self {
+            width,
+            height,
+            robots,
+        }
+    }
 
-impl<const HEIGHT: isize, const WIDTH: isize> RobotGrid<HEIGHT, WIDTH> {
-    fn quadrant(position: Position) -> Option<(isize, isize)> {
-        let qx = (position.x - crate::WIDTH / 2).signum();
-        let qy = (position.y - crate::HEIGHT / 2).signum();
+    fn parse(input: &str, width: isize, height: isize) -> Result<Self, ParseIntError> {
+        input
+            .lines()
+            .map(str::parse)
+            .collect::<Result<Vec<Robot>, _>>()
+            .map(|robots| Self::new(width, height, robots))
+    }
+
+    fn quadrant(&self, position: Position) -> Option<(isize, isize)> {
+        let qx = (position.x - self.width / 2).signum();
+        let qy = (position.y - self.height / 2).signum();
 
         if qx != 0 && qy != 0 {
             Some((qx, qy))
@@ -53,52 +79,170 @@ impl<const HEIGHT: isize, const WIDTH: isize> RobotGrid<HEIGHT, WIDTH> {
         }
     }
 
-    fn robot_position(robot: Robot, seconds: isize) -> Position {
+    fn robot_position(&self, robot: Robot, seconds: isize) -> Position {
         let p = robot.position + robot.velocity * seconds;
-        Position::new(p.x.rem_euclid(WIDTH), p.y.rem_euclid(HEIGHT))
+        Position::new(p.x.rem_euclid(self.width), p.y.rem_euclid(self.height))
     }
 
     fn safety_factor(&self, seconds: isize) -> usize {
-        self.0
+        self.robots
             .iter()
-            .filter_map(|&r| Self::quadrant(Self::robot_position(r, seconds)))
+            .filter_map(|&r| self.quadrant(self.robot_position(r, seconds)))
             .collect::<Counter<(isize, isize)>>()
             .values()
             .product()
     }
 
-    fn next(&mut self) -> &Self {
-        for r in &mut self.0 {
-            *r = Robot::new(Self::robot_position(*r, 1), r.velocity);
+    // Variance, scaled by the (constant) number of robots squared.
+    fn spread(values: impl Iterator<Item = isize>) -> isize {
+        let (count, sum, sum_squares) = values.fold((0, 0, 0), |(count, sum, sum_squares), v| {
+            (count + 1, sum + v, sum_squares + v * v)
+        });
+
+        count * sum_squares - sum * sum
+    }
+
+    // The horizontal positions repeat every `width` seconds, and the vertical ones every `height`
+    // seconds. A picture shows up as an unusually low spread along both axes, so find the best
+    // offset for each axis independently, then combine them (Chinese remainder theorem). That
+    // only works when both periods are coprime; otherwise, the whole cycle of lcm(width, height)
+    // seconds is scanned for the lowest combined spread.
+    fn most_ordered_second(&self) -> Option<isize> {
+        let spread_x = |seconds| {
+            Self::spread(
+                self.robots
+                    .iter()
+                    .map(|&r| self.robot_position(r, seconds).x),
+            )
+        };
+        let spread_y = |seconds| {
+            Self::spread(
+                self.robots
+                    .iter()
+                    .map(|&r| self.robot_position(r, seconds).y),
+            )
+        };
+
+        let gcd = gcd(self.width, self.height);
+        if gcd != 1 {
+            let lcm = self.width / gcd * self.height;
+            return (0..lcm).min_by_key(|&seconds| spread_x(seconds) + spread_y(seconds));
         }
 
-        self
+        let best_x = (0..self.width).min_by_key(|&seconds| spread_x(seconds))?;
+        let best_y = (0..self.height).min_by_key(|&seconds| spread_y(seconds))?;
+
+        (0..self.height)
+            .map(|k| best_x + k * self.width)
+            .find(|seconds| seconds % self.height == best_y)
     }
-}
 
-impl<const HEIGHT: isize, const WIDTH: isize> FromStr for RobotGrid<HEIGHT, WIDTH> {
-    type Err = ParseIntError;
+    // Number of robots on each tile, row by row.
+    fn density(&self, seconds: isize) -> Vec<Vec<usize>> {
+        let line = vec![0; self.width as usize];
+        let mut density = vec![line; self.height as usize];
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        s.lines()
-            .map(str::parse)
-            .collect::<Result<Vec<Robot>, _>>()
-            .map(Self)
+        for &r in &self.robots {
+            let p = self.robot_position(r, seconds);
+            density[p.y as usize][p.x as usize] += 1;
+        }
+
+        density
+    }
+
+    // Plain (ASCII) bitmap, black where there is at least one robot.
+    fn to_pbm(&self, seconds: isize) -> String {
+        let rows: Vec<Vec<usize>> = self
+            .density(seconds)
+            .into_iter()
+            .map(|row| {
+                row.into_iter()
+                    .map(|count| usize::from(count > 0))
+                    .collect()
+            })
+            .collect();
+
+        plain_image("P1", None, &rows)
+    }
+
+    // Plain (ASCII) graymap, darker as more robots share a tile.
+    fn to_pgm(&self, seconds: isize) -> String {
+        let density = self.density(seconds);
+        let max_value = density
+            .iter()
+            .flatten()
+            .copied()
+            .max()
+            .unwrap_or_default()
+            .max(1);
+        let rows: Vec<Vec<usize>> = density
+            .into_iter()
+            .map(|row| row.into_iter().map(|count| max_value - count).collect())
+            .collect();
+
+        plain_image("P2", Some(max_value), &rows)
+    }
+
+    fn export_frames(
+        &self,
+        directory: &Path,
+        frames: Range<isize>,
+        heat_map: bool,
+    ) -> std::io::Result<()> {
+        std::fs::create_dir_all(directory)?;
+
+        for seconds in frames {
+            let (extension, image) = if heat_map {
+                ("pgm", self.to_pgm(seconds))
+            } else {
+                ("pbm", self.to_pbm(seconds))
+            };
+            std::fs::write(
+                directory.join(format!("frame_{seconds:05}.{extension}")),
+                image,
+            )?;
+        }
+
+        Ok(())
     }
 }
 
-impl<const HEIGHT: isize, const WIDTH: isize> Display for RobotGrid<HEIGHT, WIDTH> {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        let line: Vec<u8> = std::iter::repeat_n(b' ', WIDTH as usize).collect();
-        let mut s: Vec<Vec<u8>> = std::iter::repeat_n(line.clone(), HEIGHT as usize).collect();
+// Netpbm plain formats recommend lines of at most 70 characters, so long rows are wrapped.
+fn plain_image(magic: &str, max_value: Option<usize>, rows: &[Vec<usize>]) -> String {
+    let width = rows.first().map_or(0, Vec::len);
+    let mut image = format!("{magic}\n{width} {}\n", rows.len());
+    if let Some(max_value) = max_value {
+        image.push_str(&format!("{max_value}\n"));
+    }
 
-        for p in self.0.iter().map(|r| r.position).collect::<HashSet<_>>() {
-            s[p.y as usize][p.x as usize] = b'#';
+    for row in rows {
+        let mut line = String::new();
+        for pixel in row.iter().map(usize::to_string) {
+            if !line.is_empty() && line.len() + 1 + pixel.len() > 70 {
+                image.push_str(&line);
+                image.push('\n');
+                line.clear();
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(&pixel);
         }
+        image.push_str(&line);
+        image.push('\n');
+    }
+
+    image
+}
 
-        for mut line in s {
-            line.push(b'\n');
-            write!(f, "{}", &String::from_utf8(line).unwrap())?;
+impl Display for RobotGrid {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for line in self.density(0) {
+            let line: String = line
+                .into_iter()
+                .map(|count| if count > 0 { '#' } else { ' ' })
+                .collect();
+            writeln!(f, "{line}")?;
         }
 
         Ok(())
@@ -106,25 +250,49 @@ impl<const HEIGHT: isize, const WIDTH: isize> Display for RobotGrid<HEIGHT, WIDT
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut positional = Vec::new();
+    let mut export = None;
+    let mut frames = None;
+    let mut heat_map = false;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--export" => export = Some(PathBuf::from(args.next().ok_or("Missing directory.")?)),
+            "--frames" => {
+                let range = args.next().ok_or("Missing frame range.")?;
+                let (start, end) = range.split_once("..").ok_or("Invalid frame range.")?;
+                frames = Some(start.parse()?..end.parse()?);
+            }
+            "--heat-map" => heat_map = true,
+            _ => positional.push(arg),
+        }
+    }
+    let (width, height) = match positional.as_slice() {
+        [] => (WIDTH, HEIGHT),
+        [width, height] => (width.parse()?, height.parse()?),
+        _ => return Err(USAGE.into()),
+    };
+    if width <= 0 || height <= 0 {
+        return Err("The width and height must be positive.".into());
+    }
+
     let input = std::fs::read_to_string(INPUT)?;
-    let mut robots = input.parse::<RobotGrid<HEIGHT, WIDTH>>()?;
+    let robots = RobotGrid::parse(&input, width, height)?;
 
     println!("The first answer is: {}", robots.safety_factor(100));
     println!(
         "The second answer is: {}",
-        (0..)
-            .find(|_| {
-                let answer = robots
-                    .to_string()
-                    .lines()
-                    // This is the top (and bottom) of the Christmas tree.
-                    .any(|line| line.contains("###############################"));
-                robots.next();
-                answer
-            })
-            .unwrap()
+        robots
+            .most_ordered_second()
+            .ok_or("No ordered arrangement found.")?
     );
 
+    if let Some(directory) = export {
+        // Every arrangement repeats after `width * height` seconds.
+        let frames = frames.unwrap_or(0..width * height);
+        robots.export_frames(&directory, frames, heat_map)?;
+    }
+
     Ok(())
 }
 
@@ -149,10 +317,96 @@ mod test {
 
     #[test]
     fn part1_example() {
-        let robots = EXAMPLE.parse::<RobotGrid<7, 11>>().unwrap();
+        let robots = RobotGrid::parse(EXAMPLE, 11, 7).unwrap();
         let actual = robots.safety_factor(100);
         let expected = 12;
 
         assert_eq!(expected, actual);
     }
+
+    #[test]
+    fn example_display() {
+        let robots = RobotGrid::parse("p=2,4 v=2,-3\np=12,-1 v=0,0", 11, 7).unwrap();
+        let actual = robots.to_string();
+        let expected = [
+            "           ",
+            "           ",
+            "           ",
+            "           ",
+            "  #        ",
+            "           ",
+            " #         ",
+        ]
+        .map(|line| format!("{line}\n"))
+        .concat();
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn example_pbm() {
+        let robots = RobotGrid::parse("p=0,0 v=1,1\np=2,1 v=-1,0", 3, 2).unwrap();
+        let actual = robots.to_pbm(1);
+        let expected = "P1\n3 2\n0 0 0\n0 1 0\n";
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn example_pgm() {
+        let robots = RobotGrid::parse("p=0,0 v=1,1\np=2,1 v=-1,0\np=1,0 v=0,0", 3, 2).unwrap();
+        let actual = robots.to_pgm(1);
+        let expected = "P2\n3 2\n2\n2 1 2\n2 0 2\n";
+
+        assert_eq!(expected, actual);
+    }
+
+    // Pseudo-random velocities, with every robot inside a small square after `seconds`.
+    fn synthetic_picture(width: isize, height: isize, seconds: isize) -> RobotGrid {
+        let mut seed: isize = 12345;
+        let mut random = move |modulo: isize| {
+            seed = (seed * 1103515245 + 12345).rem_euclid(1 << 31);
+            seed % modulo
+        };
+        let size = width.min(height) / 5;
+        let robots = (0..500)
+            .map(|_| {
+                let position = Position::new(width / 2 + random(size), height / 3 + random(size));
+                let velocity = Direction::new(
+                    random(2 * width - 1) - width + 1,
+                    random(2 * height - 1) - height + 1,
+                );
+                Robot { position, velocity }
+            })
+            .collect();
+        let mut robots = RobotGrid::new(width, height, robots);
+        robots.robots = robots
+            .robots
+            .iter()
+            .map(|&r| Robot {
+                position: robots.robot_position(r, -seconds),
+                velocity: r.velocity,
+            })
+            .collect();
+        robots
+    }
+
+    #[test]
+    fn part2_synthetic_picture() {
+        let robots = synthetic_picture(WIDTH, HEIGHT, 4321);
+        let actual = robots.most_ordered_second();
+        let expected = Some(4321);
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn part2_non_coprime_sizes() {
+        for (width, height, seconds) in [(100, 100, 77), (60, 40, 97)] {
+            let robots = synthetic_picture(width, height, seconds);
+            let actual = robots.most_ordered_second();
+
+            assert_eq!(Some(seconds), actual, "{width}x{height}");
+        }
+    }
 }
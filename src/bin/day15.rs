@@ -1,6 +1,5 @@
 use advent_of_code_2024::grid::{Direction, Grid, Position};
 use std::collections::VecDeque;
-use std::fmt::Debug;
 
 const INPUT: &str = "./input/day15.txt";
 
@@ -8,192 +7,122 @@ const INPUT: &str = "./input/day15.txt";
 enum Tile {
     Wall,
     Empty,
-    Box,
+    // Part of a box spanning `width` tiles, `offset` tiles from its left edge.
+    Box { offset: usize, width: usize },
 }
 
-impl TryFrom<char> for Tile {
-    type Error = char;
-
-    fn try_from(value: char) -> Result<Self, Self::Error> {
-        match value {
-            '#' => Ok(Tile::Wall),
-            '.' | '@' => Ok(Tile::Empty),
-            'O' => Ok(Tile::Box),
-            _ => Err(value),
-        }
-    }
-}
-
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-enum LargeTile {
-    Wall,
-    Empty,
-    LeftBox,
-    RightBox,
-}
-
-impl TryFrom<char> for LargeTile {
-    type Error = char;
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Warehouse(Grid<Tile>);
+
+impl Warehouse {
+    // Every tile is widened `scale` times, with boxes becoming `scale` tiles wide.
+    fn parse(warehouse: &str, scale: usize) -> Result<Self, char> {
+        let height = warehouse.lines().count();
+        let width = warehouse.lines().next().unwrap_or_default().len() * scale;
+        let grid = warehouse
+            .lines()
+            .flat_map(|line| line.chars())
+            .map(|c| match c {
+                '#' => Ok(vec![Tile::Wall; scale]),
+                '.' | '@' => Ok(vec![Tile::Empty; scale]),
+                'O' => Ok((0..scale)
+                    .map(|offset| Tile::Box {
+                        offset,
+                        width: scale,
+                    })
+                    .collect()),
+                _ => Err(c),
+            })
+            .collect::<Result<Vec<Vec<Tile>>, _>>()?
+            .concat();
 
-    fn try_from(value: char) -> Result<Self, Self::Error> {
-        match value {
-            '#' => Ok(LargeTile::Wall),
-            '.' | '@' => Ok(LargeTile::Empty),
-            '[' => Ok(LargeTile::LeftBox),
-            ']' => Ok(LargeTile::RightBox),
-            _ => Err(value),
-        }
+        Ok(Self(Grid::new(height, width, grid).unwrap()))
     }
-}
 
-pub trait Warehouse {
-    fn sum_gps_coordinates(&self) -> usize;
-    fn push(&mut self, position: Position, direction: Direction) -> bool;
-    fn print(&self) -> Vec<Vec<char>>;
-}
-
-impl Warehouse for Grid<Tile> {
     fn sum_gps_coordinates(&self) -> usize {
-        self.iter()
-            .filter_map(|(p, v)| {
-                if *v == Tile::Box {
-                    Some(p.x() + 100 * p.y())
-                } else {
-                    None
-                }
+        self.0
+            .iter()
+            .filter_map(|(p, v)| match v {
+                Tile::Box { offset: 0, .. } => Some(p.x() + 100 * p.y()),
+                _ => None,
             })
             .sum()
     }
 
     fn push(&mut self, position: Position, direction: Direction) -> bool {
-        match self.get(position) {
-            Some(Tile::Wall) => false,
-            Some(Tile::Empty) => true,
-            Some(Tile::Box) => {
-                let next_position = (position + direction).unwrap();
-                if self.push(next_position, direction) {
-                    *self.get_mut(next_position).unwrap() = Tile::Box;
-                    *self.get_mut(position).unwrap() = Tile::Empty;
-                    true
-                } else {
-                    false
+        // Find every box that needs to move (by their left edge), checking that none of them
+        // would hit a wall.
+        let mut boxes = Vec::new();
+        let mut to_visit = VecDeque::new();
+        to_visit.push_back(position);
+
+        while let Some(position) = to_visit.pop_front() {
+            match self.0.get(position) {
+                Some(Tile::Wall) | None => return false,
+                Some(Tile::Empty) => (),
+                Some(&Tile::Box { offset, width }) => {
+                    let left = Position::new(position.x() - offset, position.y());
+                    if !boxes.contains(&(left, width)) {
+                        boxes.push((left, width));
+                        // Don't bother with the tiles the box already occupies (when pushing
+                        // sideways).
+                        let span = left.x()..left.x() + width;
+                        to_visit.extend(
+                            span.clone()
+                                .map(|idx| (Position::new(idx, left.y()) + direction).unwrap())
+                                .filter(|p| p.y() != left.y() || !span.contains(&p.x())),
+                        );
+                    }
                 }
             }
-            None => false,
         }
+
+        // Lift all the boxes first, so they can't overwrite each other when put back down.
+        let tiles: Vec<(Position, Tile)> = boxes
+            .into_iter()
+            .flat_map(|(left, width)| {
+                (0..width).map(move |offset| {
+                    (
+                        Position::new(left.x() + offset, left.y()),
+                        Tile::Box { offset, width },
+                    )
+                })
+            })
+            .collect();
+        for &(position, _) in &tiles {
+            *self.0.get_mut(position).unwrap() = Tile::Empty;
+        }
+        for (position, tile) in tiles {
+            *self.0.get_mut((position + direction).unwrap()).unwrap() = tile;
+        }
+
+        true
     }
 
     fn print(&self) -> Vec<Vec<char>> {
-        self.rows()
+        self.0
+            .rows()
             .map(|line| {
-                line.map(|t| match t {
+                line.map(|t| match *t {
                     Tile::Wall => '#',
                     Tile::Empty => '.',
-                    Tile::Box => 'O',
+                    Tile::Box { width: 1, .. } => 'O',
+                    Tile::Box { offset: 0, .. } => '[',
+                    Tile::Box { offset, width } if offset + 1 == width => ']',
+                    Tile::Box { .. } => '=',
                 })
                 .collect::<Vec<char>>()
             })
             .collect()
     }
-}
 
-impl Warehouse for Grid<LargeTile> {
-    fn sum_gps_coordinates(&self) -> usize {
-        self.iter()
-            .filter_map(|(p, v)| {
-                if *v == LargeTile::LeftBox {
-                    Some(p.x() + 100 * p.y())
-                } else {
-                    None
-                }
-            })
-            .sum()
-    }
+    fn render(&self, robot: Position) -> String {
+        let mut lines = self.print();
+        lines[robot.y()][robot.x()] = '@';
 
-    fn push(&mut self, position: Position, direction: Direction) -> bool {
-        // We first have to check whether we can push. The `Tile` version works for left and
-        // right, but not up and down, as we have wide boxes.
-        if direction == Direction::Left || direction == Direction::Right {
-            match self.get(position) {
-                Some(LargeTile::Wall) => false,
-                Some(LargeTile::Empty) => true,
-                Some(&tile @ LargeTile::LeftBox) | Some(&tile @ LargeTile::RightBox) => {
-                    let next_position = (position + direction).unwrap();
-                    if self.push(next_position, direction) {
-                        *self.get_mut(next_position).unwrap() = tile;
-                        *self.get_mut(position).unwrap() = LargeTile::Empty;
-                        true
-                    } else {
-                        false
-                    }
-                }
-                None => false,
-            }
-        } else {
-            // Use a VecDeque to make sure we visit the tiles in order (up or down), which is
-            // important when it comes to emptying `to_push` afterward. Otherwise, we end up
-            // moving boxes that still can't be moved.
-            let mut to_push = Vec::new();
-            let mut to_visit = VecDeque::new();
-            to_visit.push_back(position);
-
-            // Check whether we can push, and record those we need to push.
-            while let Some(position) = to_visit.pop_front() {
-                match self.get(position) {
-                    Some(LargeTile::Wall) => return false,
-                    Some(LargeTile::Empty) => (),
-                    Some(LargeTile::LeftBox) => {
-                        // Check that we haven't accounted for it. Otherwise, we'll be pushing
-                        // the same tile twice (once full, once empty). Everything breaks.
-                        if !to_push.contains(&position) {
-                            to_push.push(position);
-                            to_visit.push_back((position + direction).unwrap());
-                        }
-                        let right = (position + Direction::Right).unwrap();
-                        if !to_push.contains(&right) {
-                            to_push.push(right);
-                            to_visit.push_back((right + direction).unwrap());
-                        }
-                    }
-                    Some(LargeTile::RightBox) => {
-                        // Check that we haven't accounted for it. Otherwise, we'll be pushing
-                        // the same tile twice (once full, once empty). Everything breaks.
-                        if !to_push.contains(&position) {
-                            to_push.push(position);
-                            to_visit.push_back((position + direction).unwrap());
-                        }
-                        let left = (position + Direction::Left).unwrap();
-                        if !to_push.contains(&left) {
-                            to_push.push(left);
-                            to_visit.push_back((left + direction).unwrap());
-                        }
-                    }
-                    None => unreachable!("We should hit a wall first."),
-                }
-            }
-
-            // If we haven't hit a wall, we're good to go.
-            while let Some(position) = to_push.pop() {
-                let current = *self.get(position).unwrap();
-                *self.get_mut((position + direction).unwrap()).unwrap() = current;
-                *self.get_mut(position).unwrap() = LargeTile::Empty;
-            }
-
-            true
-        }
-    }
-
-    fn print(&self) -> Vec<Vec<char>> {
-        self.rows()
-            .map(|line| {
-                line.map(|t| match t {
-                    LargeTile::Wall => '#',
-                    LargeTile::Empty => '.',
-                    LargeTile::LeftBox => '[',
-                    LargeTile::RightBox => ']',
-                })
-                .collect::<Vec<char>>()
-            })
+        lines
+            .into_iter()
+            .flat_map(|line| line.into_iter().chain(std::iter::once('\n')))
             .collect()
     }
 }
@@ -212,77 +141,83 @@ impl Robot {
         }
     }
 
-    fn operate<T: Warehouse + Debug>(&mut self, warehouse: &mut T) -> &mut Self {
+    fn step(position: Position, direction: Direction, warehouse: &mut Warehouse) -> Position {
+        let next_position = (position + direction).unwrap();
+        if warehouse.push(next_position, direction) {
+            next_position
+        } else {
+            position
+        }
+    }
+
+    fn operate(&mut self, warehouse: &mut Warehouse) -> &mut Self {
         for &direction in &self.directions {
-            let next_position = (self.position + direction).unwrap();
-            if warehouse.push(next_position, direction) {
-                self.position = next_position;
-            }
+            self.position = Self::step(self.position, direction, warehouse);
         }
 
         self
     }
+
+    // Replay the moves on a copy of the warehouse, yielding a frame every `every` moves (and
+    // after the last one), stopping after `stop` moves if given.
+    fn replay(&self, warehouse: &Warehouse, every: usize, stop: Option<usize>) -> Replay<'_> {
+        let stop = stop.map_or(self.directions.len(), |stop| {
+            stop.min(self.directions.len())
+        });
+
+        Replay {
+            directions: &self.directions[..stop],
+            every: every.max(1),
+            frame: Frame {
+                moves: 0,
+                position: self.position,
+                warehouse: warehouse.clone(),
+            },
+        }
+    }
 }
 
-fn part1(input: &str) -> usize {
-    let (warehouse, directions) = input.split_once("\n\n").unwrap();
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Frame {
+    moves: usize,
+    position: Position,
+    warehouse: Warehouse,
+}
 
-    let robot_position = warehouse
-        .lines()
-        .enumerate()
-        .find_map(|(idy, line)| {
-            line.chars()
-                .position(|c| c == '@')
-                .map(|idx| Position::new(idx, idy))
-        })
-        .unwrap();
+impl std::fmt::Display for Frame {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.warehouse.render(self.position))
+    }
+}
 
-    let height = warehouse.lines().count();
-    let width = warehouse.lines().next().unwrap_or_default().len();
-    let grid = warehouse
-        .lines()
-        .flat_map(|line| line.chars().map(Tile::try_from))
-        .collect::<Result<Vec<Tile>, _>>()
-        .unwrap();
-    let mut warehouse = Grid::new(height, width, grid).unwrap();
+#[derive(Debug, Clone)]
+struct Replay<'a> {
+    directions: &'a [Direction],
+    every: usize,
+    frame: Frame,
+}
 
-    let directions = directions
-        .lines()
-        .flat_map(str::chars)
-        .map(|c| match c {
-            '^' => Direction::Up,
-            '<' => Direction::Left,
-            '>' => Direction::Right,
-            'v' => Direction::Down,
-            _ => panic!("Invalid character '{}'", c),
-        })
-        .collect::<Vec<_>>();
-    let mut robot = Robot::new(robot_position, directions);
+impl Iterator for Replay<'_> {
+    type Item = Frame;
 
-    robot.operate(&mut warehouse);
-    warehouse.sum_gps_coordinates()
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.frame.moves >= self.directions.len() {
+            return None;
+        }
+
+        let target = (self.frame.moves + self.every).min(self.directions.len());
+        for &direction in &self.directions[self.frame.moves..target] {
+            self.frame.position =
+                Robot::step(self.frame.position, direction, &mut self.frame.warehouse);
+        }
+        self.frame.moves = target;
+
+        Some(self.frame.clone())
+    }
 }
 
-fn part2(input: &str) -> usize {
+fn parse_input(input: &str, scale: usize) -> (Warehouse, Robot) {
     let (warehouse, directions) = input.split_once("\n\n").unwrap();
-    let warehouse = warehouse
-        .lines()
-        .map(|line| {
-            line.chars()
-                .flat_map(|c| {
-                    match c {
-                        '#' => "##",
-                        '.' => "..",
-                        '@' => "@.",
-                        'O' => "[]",
-                        _ => panic!("Invalid character {c}."),
-                    }
-                    .chars()
-                })
-                .chain(std::iter::once('\n'))
-                .collect::<String>()
-        })
-        .collect::<String>();
 
     let robot_position = warehouse
         .lines()
@@ -290,19 +225,10 @@ fn part2(input: &str) -> usize {
         .find_map(|(idy, line)| {
             line.chars()
                 .position(|c| c == '@')
-                .map(|idx| Position::new(idx, idy))
+                .map(|idx| Position::new(idx * scale, idy))
         })
         .unwrap();
 
-    let height = warehouse.lines().count();
-    let width = warehouse.lines().next().unwrap_or_default().len();
-    let grid = warehouse
-        .lines()
-        .flat_map(|line| line.chars().map(LargeTile::try_from))
-        .collect::<Result<Vec<LargeTile>, _>>()
-        .unwrap();
-    let mut warehouse = Grid::new(height, width, grid).unwrap();
-
     let directions = directions
         .lines()
         .flat_map(str::chars)
@@ -314,15 +240,62 @@ fn part2(input: &str) -> usize {
             _ => panic!("Invalid character '{}'", c),
         })
         .collect::<Vec<_>>();
-    let mut robot = Robot::new(robot_position, directions);
+
+    (
+        Warehouse::parse(warehouse, scale).unwrap(),
+        Robot::new(robot_position, directions),
+    )
+}
+
+fn sum_gps_coordinates(input: &str, scale: usize) -> usize {
+    let (mut warehouse, mut robot) = parse_input(input, scale);
 
     robot.operate(&mut warehouse);
     warehouse.sum_gps_coordinates()
 }
 
+fn part1(input: &str) -> usize {
+    sum_gps_coordinates(input, 1)
+}
+
+fn part2(input: &str) -> usize {
+    sum_gps_coordinates(input, 2)
+}
+
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let input = std::fs::read_to_string(INPUT)?;
 
+    // Usage: day15 [--replay] [--scale N] [--every N] [--stop MOVES]
+    let mut replay = false;
+    let mut scale = 1;
+    let mut every = 1;
+    let mut stop = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--replay" => replay = true,
+            "--scale" => {
+                scale = args.next().ok_or("Missing scale.")?.parse()?;
+                if scale == 0 {
+                    return Err("The scale must be at least 1.".into());
+                }
+            }
+            "--every" => every = args.next().ok_or("Missing frame interval.")?.parse()?,
+            "--stop" => stop = Some(args.next().ok_or("Missing move index.")?.parse()?),
+            _ => {
+                return Err("Usage: day15 [--replay] [--scale N] [--every N] [--stop MOVES]".into())
+            }
+        }
+    }
+
+    if replay {
+        let (warehouse, robot) = parse_input(&input, scale);
+        println!("Initial state:\n{}", warehouse.render(robot.position));
+        for frame in robot.replay(&warehouse, every, stop) {
+            println!("After {} moves:\n{}", frame.moves, frame);
+        }
+    }
+
     println!("The first answer is: {}", part1(&input));
     println!("The second answer is: {}", part2(&input));
 
@@ -413,4 +386,113 @@ mod test {
 
         assert_eq!(expected, actual);
     }
+
+    #[test]
+    fn replay_small_example() {
+        let (warehouse, robot) = parse_input(SMALL_EXAMPLE, 1);
+        let frames: Vec<String> = robot
+            .replay(&warehouse, 2, Some(4))
+            .map(|frame| frame.to_string())
+            .collect();
+        let expected = vec![
+            "\
+            ########\n\
+            #.@O.O.#\n\
+            ##..O..#\n\
+            #...O..#\n\
+            #.#.O..#\n\
+            #...O..#\n\
+            #......#\n\
+            ########\n\
+            ",
+            "\
+            ########\n\
+            #..@OO.#\n\
+            ##..O..#\n\
+            #...O..#\n\
+            #.#.O..#\n\
+            #...O..#\n\
+            #......#\n\
+            ########\n\
+            ",
+        ];
+
+        assert_eq!(expected, frames);
+    }
+
+    #[test]
+    fn replay_small_example_last_frame() {
+        let (warehouse, robot) = parse_input(SMALL_EXAMPLE, 1);
+        let actual = robot.replay(&warehouse, 1, None).last().unwrap();
+        let expected = "\
+            ########\n\
+            #....OO#\n\
+            ##.....#\n\
+            #.....O#\n\
+            #.#O@..#\n\
+            #...O..#\n\
+            #...O..#\n\
+            ########\n\
+            ";
+
+        assert_eq!(15, actual.moves);
+        assert_eq!(expected, actual.to_string());
+    }
+
+    #[test]
+    fn replay_wide_example_last_frame() {
+        let (warehouse, robot) = parse_input(SMALL_EXAMPLE_2, 2);
+        let actual = robot.replay(&warehouse, 4, None).last().unwrap();
+        let expected = "\
+            ##############\n\
+            ##...[].##..##\n\
+            ##...@.[]...##\n\
+            ##....[]....##\n\
+            ##..........##\n\
+            ##..........##\n\
+            ##############\n\
+            ";
+
+        assert_eq!(11, actual.moves);
+        assert_eq!(expected, actual.to_string());
+    }
+
+    #[test]
+    fn scale_three() {
+        let input = "\
+            ########\n\
+            #......#\n\
+            #...O..#\n\
+            #..@O..#\n\
+            #......#\n\
+            ########\n\
+            \n\
+            >>>v>^\n\
+        ";
+        let (warehouse, robot) = parse_input(input, 3);
+        let actual = robot.replay(&warehouse, 6, None).last().unwrap();
+        let expected = "\
+            ########################\n\
+            ###.........[=]......###\n\
+            ###..........[=].....###\n\
+            ###..........@.......###\n\
+            ###..................###\n\
+            ########################\n\
+            ";
+
+        assert_eq!(expected, actual.to_string());
+        assert_eq!(12 + 100 + 13 + 200, actual.warehouse.sum_gps_coordinates());
+    }
+
+    #[test]
+    fn blocked_push() {
+        for scale in 1..=4 {
+            let (mut warehouse, mut robot) =
+                parse_input("#####\n#.O.#\n#.O.#\n#.@.#\n#####\n\n^^\n", scale);
+            let expected = (warehouse.clone(), robot.position);
+            robot.operate(&mut warehouse);
+
+            assert_eq!(expected, (warehouse, robot.position));
+        }
+    }
 }
@@ -1,12 +1,10 @@
 use advent_of_code_2024::grid::{Direction, Grid, Position};
 use std::cmp::Reverse;
+use std::collections::hash_map::Entry;
 use std::collections::{BinaryHeap, HashMap, HashSet};
 use std::str::FromStr;
 
 const INPUT: &str = "./input/day16.txt";
-const STEP_COST: u32 = 1;
-const TURN_COST: u32 = 1000;
-const START_DIRECTION: Direction = Direction::Right;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 enum Tile {
@@ -30,61 +28,135 @@ impl TryFrom<char> for Tile {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct MazeRules {
+    step_cost: u32,
+    turn_cost: u32,
+    // Turning around in place, if allowed. Otherwise, it takes two turns.
+    reverse_cost: Option<u32>,
+    start_direction: Direction,
+    // The direction the reindeer must face on the end tile, if any.
+    end_direction: Option<Direction>,
+}
+
+impl Default for MazeRules {
+    fn default() -> Self {
+        Self {
+            step_cost: 1,
+            turn_cost: 1000,
+            reverse_cost: None,
+            start_direction: Direction::Right,
+            end_direction: None,
+        }
+    }
+}
+
+impl MazeRules {
+    fn moves(
+        self,
+        position: Position,
+        direction: Direction,
+    ) -> impl Iterator<Item = (u32, Position, Direction)> {
+        [
+            (position + direction).map(|p| (self.step_cost, p, direction)),
+            Some((self.turn_cost, position, direction.rotate_left())),
+            Some((self.turn_cost, position, direction.rotate_right())),
+            self.reverse_cost
+                .map(|cost| (cost, position, direction.reverse())),
+        ]
+        .into_iter()
+        .flatten()
+    }
+
+    fn is_finished(self, direction: Direction) -> bool {
+        self.end_direction.is_none_or(|d| d == direction)
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 struct Maze(Grid<Tile>);
 
 impl Maze {
-    fn best_score(&self) -> u32 {
-        let start_position = self
-            .0
+    fn start(&self) -> Position {
+        self.0
             .iter()
             .find_map(|(p, &t)| if t == Tile::Start { Some(p) } else { None })
-            .unwrap();
-        let mut to_visit = std::iter::once((Reverse(0), start_position, START_DIRECTION))
+            .unwrap()
+    }
+
+    fn best_path(&self, rules: &MazeRules) -> Option<(u32, Vec<(Position, Direction)>)> {
+        let mut to_visit = std::iter::once((Reverse(0), self.start(), rules.start_direction, None))
             .collect::<BinaryHeap<_>>();
-        let mut visited = HashSet::new();
+        // The first time a position-direction pair is reached is along a best path, so keeping
+        // track of where we came from at that point is enough to rebuild it.
+        let mut previous = HashMap::new();
 
-        while let Some((Reverse(score), position, direction)) = to_visit.pop() {
-            if visited.insert((position, direction)) {
+        while let Some((Reverse(score), position, direction, from)) = to_visit.pop() {
+            if let Entry::Vacant(entry) = previous.entry((position, direction)) {
+                entry.insert(from);
+                let from = Some((position, direction));
                 match self.0.get(position) {
                     Some(Tile::Wall) => (),
-                    Some(Tile::End) => return score,
-                    Some(Tile::Start) | Some(Tile::Open) => {
-                        // Step forward.
-                        to_visit.push((
-                            Reverse(score + STEP_COST),
-                            (position + direction).unwrap(),
-                            direction,
-                        ));
-                        // Turn left and right.
-                        to_visit.push((
-                            Reverse(score + TURN_COST),
-                            position,
-                            direction.rotate_left(),
-                        ));
-                        to_visit.push((
-                            Reverse(score + TURN_COST),
-                            position,
-                            direction.rotate_right(),
-                        ));
+                    Some(Tile::End) if rules.is_finished(direction) => {
+                        let mut path: Vec<(Position, Direction)> =
+                            std::iter::successors(from, |state| previous[state]).collect();
+                        path.reverse();
+                        return Some((score, path));
+                    }
+                    Some(Tile::Start) | Some(Tile::Open) | Some(Tile::End) => {
+                        for (cost, p, d) in rules.moves(position, direction) {
+                            to_visit.push((Reverse(score + cost), p, d, from));
+                        }
                     }
                     None => unreachable!("The maze is surrounded by walls."),
                 }
             }
         }
 
-        0
+        None
+    }
+
+    fn best_score(&self, rules: &MazeRules) -> Option<u32> {
+        self.best_path(rules).map(|(score, _)| score)
     }
 
-    fn best_paths(&self) -> Vec<Vec<(Position, Direction)>> {
-        let best_score = self.best_score();
-        let start_position = self
+    // Draw the path over the maze, showing the direction taken when leaving each tile.
+    fn render(&self, path: &[(Position, Direction)]) -> String {
+        let mut tiles: Vec<char> = self
             .0
             .iter()
-            .find_map(|(p, &t)| if t == Tile::Start { Some(p) } else { None })
-            .unwrap();
+            .map(|(_, tile)| match tile {
+                Tile::Wall => '#',
+                Tile::Open => '.',
+                Tile::Start => 'S',
+                Tile::End => 'E',
+            })
+            .collect();
+
+        for &(position, direction) in path {
+            if self.0.get(position) == Some(&Tile::Open) {
+                tiles[position.x() + position.y() * self.0.width()] = match direction {
+                    Direction::Up => '^',
+                    Direction::Down => 'v',
+                    Direction::Left => '<',
+                    Direction::Right => '>',
+                    _ => unreachable!("The reindeer only moves orthogonally."),
+                };
+            }
+        }
+
+        tiles
+            .chunks(self.0.width())
+            .flat_map(|line| line.iter().copied().chain(std::iter::once('\n')))
+            .collect()
+    }
+
+    fn best_paths(&self, rules: &MazeRules) -> Vec<Vec<(Position, Direction)>> {
+        let Some(best_score) = self.best_score(rules) else {
+            return Vec::new();
+        };
         let mut to_visit = BinaryHeap::new();
-        to_visit.push((Reverse(0), vec![(start_position, START_DIRECTION)]));
+        to_visit.push((Reverse(0), vec![(self.start(), rules.start_direction)]));
         let mut visited = HashMap::new();
         let mut best_paths = Vec::new();
 
@@ -100,36 +172,22 @@ impl Maze {
             if *visited.entry((position, direction)).or_insert(score) == score {
                 match self.0.get(position) {
                     Some(Tile::Wall) => (),
-                    Some(Tile::End) => best_paths.push(path),
-                    Some(Tile::Start) | Some(Tile::Open) => {
+                    Some(Tile::End) if rules.is_finished(direction) => best_paths.push(path),
+                    Some(Tile::Start) | Some(Tile::Open) | Some(Tile::End) => {
                         // Using the best score allows us to cut down on the size of the queue,
                         // but it might makes sense to cut down more (checking for walls here for
                         // example).
-                        if score + STEP_COST <= best_score {
-                            let new_path = path
-                                .iter()
-                                .copied()
-                                .chain(std::iter::once((
-                                    (position + direction).unwrap(),
-                                    direction,
-                                )))
-                                .collect();
-                            to_visit.push((Reverse(score + STEP_COST), new_path));
-                        }
-                        // Turn left and right.
-                        if score + TURN_COST <= best_score {
-                            let new_path = path
-                                .iter()
-                                .copied()
-                                .chain(std::iter::once((position, direction.rotate_left())))
-                                .collect();
-                            to_visit.push((Reverse(score + TURN_COST), new_path));
-                            let new_path = path
-                                .iter()
-                                .copied()
-                                .chain(std::iter::once((position, direction.rotate_right())))
-                                .collect();
-                            to_visit.push((Reverse(score + TURN_COST), new_path));
+                        for (cost, p, d) in rules.moves(position, direction) {
+                            // Free moves (turning, with no turn cost) could otherwise go around in
+                            // circles without ever increasing the score.
+                            if score + cost <= best_score && (cost > 0 || !path.contains(&(p, d))) {
+                                let new_path = path
+                                    .iter()
+                                    .copied()
+                                    .chain(std::iter::once((p, d)))
+                                    .collect();
+                                to_visit.push((Reverse(score + cost), new_path));
+                            }
                         }
                     }
                     None => unreachable!("The maze is surrounded by walls."),
@@ -140,8 +198,8 @@ impl Maze {
         best_paths
     }
 
-    fn best_spots(&self) -> usize {
-        self.best_paths()
+    fn best_spots(&self, rules: &MazeRules) -> usize {
+        self.best_paths(rules)
             .into_iter()
             .flat_map(|path| path.into_iter().map(|(p, _)| p))
             .collect::<HashSet<Position>>()
@@ -168,9 +226,16 @@ impl FromStr for Maze {
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let input = std::fs::read_to_string(INPUT)?;
     let maze: Maze = input.parse::<Maze>().unwrap();
+    let rules = MazeRules::default();
+    let (best_score, best_path) = maze.best_path(&rules).ok_or("The end is unreachable.")?;
+
+    // Usage: day16 [--render]
+    if std::env::args().skip(1).any(|arg| arg == "--render") {
+        print!("{}", maze.render(&best_path));
+    }
 
-    println!("The first answer is: {}", maze.best_score());
-    println!("The second answer is: {}", maze.best_spots());
+    println!("The first answer is: {}", best_score);
+    println!("The second answer is: {}", maze.best_spots(&rules));
 
     Ok(())
 }
@@ -217,11 +282,19 @@ mod test {
         #################\n\
         ";
 
+    const SMALL: &str = "\
+        ######\n\
+        #...E#\n\
+        #.####\n\
+        #S...#\n\
+        ######\n\
+        ";
+
     #[test]
     fn part1_example1() {
         let maze = EXAMPLE_1.parse::<Maze>().unwrap();
-        let actual = maze.best_score();
-        let expected = 7036;
+        let actual = maze.best_score(&MazeRules::default());
+        let expected = Some(7036);
 
         assert_eq!(expected, actual);
     }
@@ -229,16 +302,89 @@ mod test {
     #[test]
     fn part1_example2() {
         let maze = EXAMPLE_2.parse::<Maze>().unwrap();
-        let actual = maze.best_score();
-        let expected = 11048;
+        let actual = maze.best_score(&MazeRules::default());
+        let expected = Some(11048);
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn unreachable_end() {
+        let maze = "#####\n#S#E#\n#####\n".parse::<Maze>().unwrap();
 
+        assert_eq!(None, maze.best_path(&MazeRules::default()));
+        assert!(maze.best_paths(&MazeRules::default()).is_empty());
+    }
+
+    #[test]
+    fn best_path_rendering() {
+        let maze = SMALL.parse::<Maze>().unwrap();
+        let (score, path) = maze.best_path(&MazeRules::default()).unwrap();
+        let actual = maze.render(&path);
+        let expected = "\
+            ######\n\
+            #>>>E#\n\
+            #^####\n\
+            #S...#\n\
+            ######\n\
+            ";
+
+        assert_eq!(2005, score);
         assert_eq!(expected, actual);
     }
 
+    #[test]
+    fn best_path_example1() {
+        let maze = EXAMPLE_1.parse::<Maze>().unwrap();
+        let rules = MazeRules::default();
+        let (score, path) = maze.best_path(&rules).unwrap();
+        let turns = path.windows(2).filter(|w| w[0].0 == w[1].0).count() as u32;
+        let steps = path.len() as u32 - 1 - turns;
+
+        assert_eq!(7036, score);
+        assert_eq!(score, steps * rules.step_cost + turns * rules.turn_cost);
+    }
+
+    #[test]
+    fn zero_turn_cost() {
+        let maze = SMALL.parse::<Maze>().unwrap();
+        let rules = MazeRules {
+            turn_cost: 0,
+            ..MazeRules::default()
+        };
+
+        assert_eq!(Some(5), maze.best_score(&rules));
+        assert_eq!(6, maze.best_spots(&rules));
+    }
+
+    #[test]
+    fn reverse_cost() {
+        let maze = "######\n#E..S#\n######\n".parse::<Maze>().unwrap();
+        let rules = MazeRules {
+            reverse_cost: Some(500),
+            ..MazeRules::default()
+        };
+
+        assert_eq!(Some(2003), maze.best_score(&MazeRules::default()));
+        assert_eq!(Some(503), maze.best_score(&rules));
+    }
+
+    #[test]
+    fn end_direction() {
+        let maze = SMALL.parse::<Maze>().unwrap();
+        let rules = MazeRules {
+            end_direction: Some(Direction::Up),
+            ..MazeRules::default()
+        };
+
+        assert_eq!(Some(3005), maze.best_score(&rules));
+        assert_eq!(6, maze.best_spots(&rules));
+    }
+
     #[test]
     fn part2_example1() {
         let maze = EXAMPLE_1.parse::<Maze>().unwrap();
-        let actual = maze.best_spots();
+        let actual = maze.best_spots(&MazeRules::default());
         let expected = 45;
 
         assert_eq!(expected, actual);
@@ -247,7 +393,7 @@ mod test {
     #[test]
     fn part2_example2() {
         let maze = EXAMPLE_2.parse::<Maze>().unwrap();
-        let actual = maze.best_spots();
+        let actual = maze.best_spots(&MazeRules::default());
         let expected = 64;
 
         assert_eq!(expected, actual);
@@ -1,6 +1,5 @@
 use advent_of_code_2024::grid::{Grid, Position};
-use std::cmp::Reverse;
-use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::collections::BTreeMap;
 use std::str::FromStr;
 
 const INPUT: &str = "./input/day20.txt";
@@ -35,95 +34,83 @@ struct Racetrack {
 }
 
 impl Racetrack {
-    fn shortest_path(&self) -> Option<usize> {
-        let mut to_visit = BinaryHeap::new();
-        let mut visited = HashSet::new();
-        to_visit.push((Reverse(0), self.start));
-        visited.insert(self.start);
-
-        while let Some((Reverse(picoseconds), pos)) = to_visit.pop() {
-            match self.track.get(pos) {
-                Some(&Tile::End) => return Some(picoseconds),
-                Some(&Tile::Wall) | None => (),
-                Some(&Tile::Track) | Some(&Tile::Start) => {
-                    to_visit.extend(pos.neighbours().filter_map(|neighbor| {
-                        if visited.insert(neighbor) {
-                            Some((Reverse(picoseconds + 1), neighbor))
-                        } else {
-                            None
-                        }
-                    }))
-                }
-            }
+    // There is a single path from start to end, so it can be followed without any search.
+    fn track(&self) -> Vec<Position> {
+        let mut track = vec![self.start];
+        let mut previous = None;
+        let mut current = self.start;
+
+        while current != self.end {
+            let next = current
+                .neighbours()
+                .find(|&p| {
+                    Some(p) != previous
+                        && matches!(
+                            self.track.get(p),
+                            Some(&Tile::Track) | Some(&Tile::End) | Some(&Tile::Start)
+                        )
+                })
+                .expect("The track should lead to the end.");
+            previous = Some(current);
+            current = next;
+            track.push(current);
         }
 
-        None
+        track
     }
 
-    fn cheats(
-        &self,
-        shortest_time: usize,
-        cheat_length: usize,
-    ) -> HashMap<(Position, Position), usize> {
-        // Precompute the shortest path to the end.
-        let mut shortest_paths = HashMap::new();
-        let mut to_visit = BinaryHeap::new();
-        to_visit.push((Reverse(0), self.end));
-
-        while let Some((Reverse(picoseconds), pos)) = to_visit.pop() {
-            match self.track.get(pos) {
-                Some(&Tile::Track) | Some(&Tile::End) | Some(&Tile::Start) => {
-                    if !shortest_paths.contains_key(&pos) {
-                        shortest_paths.entry(pos).or_insert(picoseconds);
-                        to_visit.extend(
-                            pos.neighbours()
-                                .map(|neighbor| (Reverse(picoseconds + 1), neighbor)),
-                        );
-                    }
-                }
-                Some(&Tile::Wall) | None => (),
-            }
+    // Number of cheats for each amount of picoseconds saved.
+    fn cheats(&self, cheat_length: usize) -> BTreeMap<usize, usize> {
+        let track = self.track();
+        let mut indices = vec![None; self.track.height() * self.track.width()];
+        for (idx, p) in track.iter().enumerate() {
+            indices[p.x() + p.y() * self.track.width()] = Some(idx);
         }
+        let indices = Grid::new(self.track.height(), self.track.width(), indices).unwrap();
+
+        // Only look at the ending points within reach of each beginning point, keeping the cheats
+        // that actually shorten the path (but do include the distance traveled while cheating!).
+        let radius = cheat_length as isize;
+        let mut cheats = BTreeMap::new();
+        for (start_idx, &start) in track.iter().enumerate() {
+            for dy in -radius..=radius {
+                let remaining = radius - dy.abs();
+                for dx in -remaining..=remaining {
+                    let end = start
+                        .x()
+                        .checked_add_signed(dx)
+                        .zip(start.y().checked_add_signed(dy))
+                        .map(|(x, y)| Position::new(x, y));
+                    let Some(&Some(end_idx)) = end.and_then(|end| indices.get(end)) else {
+                        continue;
+                    };
 
-        // Precompute the shortest distance from the start.
-        let mut shortest_distances = HashMap::new();
-        let mut to_visit = BinaryHeap::new();
-        to_visit.push((Reverse(0), self.start));
-
-        while let Some((Reverse(picoseconds), pos)) = to_visit.pop() {
-            match self.track.get(pos) {
-                Some(&Tile::Start) | Some(&Tile::Track) | Some(&Tile::End) => {
-                    if !shortest_distances.contains_key(&pos) {
-                        shortest_distances.entry(pos).or_insert(picoseconds);
-                        to_visit.extend(
-                            pos.neighbours()
-                                .map(|neighbor| (Reverse(picoseconds + 1), neighbor)),
-                        );
+                    let distance = dx.unsigned_abs() + dy.unsigned_abs();
+                    if let Some(saved) = end_idx
+                        .checked_sub(start_idx + distance)
+                        .filter(|&saved| saved > 0)
+                    {
+                        *cheats.entry(saved).or_insert(0) += 1;
                     }
                 }
-                Some(&Tile::Wall) | None => (),
             }
         }
 
-        // Now, compare every reachable beginning and ending points for the cheats, keeping only:
-        // - that are short enough (beginning end ending points are at most the given picoseconds
-        //   apart.
-        // - that actually shorten the path below the non-cheating shortest path (but do include
-        //   the distance traveled while cheating!).
-        shortest_distances
-            .iter()
-            .flat_map(|(&start, &distance)| {
-                shortest_paths.iter().filter_map(move |(&end, &path)| {
-                    shortest_time
-                        .checked_sub(path + distance + end.manhattan_distance(start))
-                        .filter(|c| *c > 0 && end.manhattan_distance(start) <= cheat_length)
-                        .map(|c| ((start, end), c))
-                })
-            })
-            .collect()
+        cheats
     }
 }
 
+// Grouped as in the puzzle examples, ordered by picoseconds saved.
+fn report(cheats: &BTreeMap<usize, usize>, threshold: usize) -> String {
+    cheats
+        .range(threshold..)
+        .map(|(saved, count)| match count {
+            1 => format!("There is one cheat that saves {saved} picoseconds.\n"),
+            _ => format!("There are {count} cheats that save {saved} picoseconds.\n"),
+        })
+        .collect()
+}
+
 impl FromStr for Racetrack {
     type Err = String;
 
@@ -167,22 +154,31 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let input = std::fs::read_to_string(INPUT)?;
     let racetrack = input.parse::<Racetrack>()?;
 
-    let shortest_time = racetrack.shortest_path().unwrap();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.as_slice() {
+        [] => (),
+        [flag, cheat_length, threshold] if flag == "--report" => {
+            let cheats = racetrack.cheats(cheat_length.parse()?);
+            print!("{}", report(&cheats, threshold.parse()?));
+        }
+        _ => return Err("Usage: day20 [--report CHEAT_LENGTH THRESHOLD]".into()),
+    }
+
     println!(
         "The first answer is: {}",
         racetrack
-            .cheats(shortest_time, 2)
-            .into_iter()
-            .filter(|(_, picoseconds)| *picoseconds >= 100)
-            .count()
+            .cheats(2)
+            .range(100..)
+            .map(|(_, count)| count)
+            .sum::<usize>()
     );
     println!(
-        "The first answer is: {}",
+        "The second answer is: {}",
         racetrack
-            .cheats(shortest_time, 20)
-            .into_iter()
-            .filter(|(_, picoseconds)| *picoseconds >= 100)
-            .count()
+            .cheats(20)
+            .range(100..)
+            .map(|(_, count)| count)
+            .sum::<usize>()
     );
 
     Ok(())
@@ -214,16 +210,24 @@ mod tests {
     fn test_part1() {
         let racetrack = EXAMPLE.parse::<Racetrack>().unwrap();
 
-        let shortest_path = racetrack.shortest_path().unwrap();
+        let shortest_path = racetrack.track().len() - 1;
         let expected = 84;
         assert_eq!(expected, shortest_path);
 
-        let mut actual: Vec<usize> = racetrack.cheats(shortest_path, 2).into_values().collect();
-        actual.sort_unstable();
-        let expected = vec![
-            2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 6,
-            6, 8, 8, 8, 8, 10, 10, 12, 12, 12, 20, 36, 38, 40, 64,
-        ];
+        let actual = racetrack.cheats(2);
+        let expected = BTreeMap::from([
+            (2, 14),
+            (4, 14),
+            (6, 2),
+            (8, 4),
+            (10, 2),
+            (12, 3),
+            (20, 1),
+            (36, 1),
+            (38, 1),
+            (40, 1),
+            (64, 1),
+        ]);
 
         assert_eq!(expected, actual);
     }
@@ -232,17 +236,62 @@ mod tests {
     fn test_part2() {
         let racetrack = EXAMPLE.parse::<Racetrack>().unwrap();
 
-        let shortest_path = racetrack.shortest_path().unwrap();
+        let shortest_path = racetrack.track().len() - 1;
         let expected = 84;
         assert_eq!(expected, shortest_path);
 
         let actual: usize = racetrack
-            .cheats(shortest_path, 20)
-            .into_values()
-            .filter(|c| *c >= 50)
+            .cheats(20)
+            .range(50..)
+            .map(|(saved, count)| saved * count)
             .sum();
         let expected = 16940;
 
         assert_eq!(expected, actual);
     }
+
+    #[test]
+    fn part1_report() {
+        let racetrack = EXAMPLE.parse::<Racetrack>().unwrap();
+        let actual = report(&racetrack.cheats(2), 1);
+        let expected = "\
+            There are 14 cheats that save 2 picoseconds.\n\
+            There are 14 cheats that save 4 picoseconds.\n\
+            There are 2 cheats that save 6 picoseconds.\n\
+            There are 4 cheats that save 8 picoseconds.\n\
+            There are 2 cheats that save 10 picoseconds.\n\
+            There are 3 cheats that save 12 picoseconds.\n\
+            There is one cheat that saves 20 picoseconds.\n\
+            There is one cheat that saves 36 picoseconds.\n\
+            There is one cheat that saves 38 picoseconds.\n\
+            There is one cheat that saves 40 picoseconds.\n\
+            There is one cheat that saves 64 picoseconds.\n\
+        ";
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn part2_report() {
+        let racetrack = EXAMPLE.parse::<Racetrack>().unwrap();
+        let actual = report(&racetrack.cheats(20), 50);
+        let expected = "\
+            There are 32 cheats that save 50 picoseconds.\n\
+            There are 31 cheats that save 52 picoseconds.\n\
+            There are 29 cheats that save 54 picoseconds.\n\
+            There are 39 cheats that save 56 picoseconds.\n\
+            There are 25 cheats that save 58 picoseconds.\n\
+            There are 23 cheats that save 60 picoseconds.\n\
+            There are 20 cheats that save 62 picoseconds.\n\
+            There are 19 cheats that save 64 picoseconds.\n\
+            There are 12 cheats that save 66 picoseconds.\n\
+            There are 14 cheats that save 68 picoseconds.\n\
+            There are 12 cheats that save 70 picoseconds.\n\
+            There are 22 cheats that save 72 picoseconds.\n\
+            There are 4 cheats that save 74 picoseconds.\n\
+            There are 3 cheats that save 76 picoseconds.\n\
+        ";
+
+        assert_eq!(expected, actual);
+    }
 }
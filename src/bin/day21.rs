@@ -1,250 +1,312 @@
-use advent_of_code_2024::grid::Position;
-use counter::Counter;
-use std::cmp::Ordering;
-use std::fmt::Debug;
+use advent_of_code_2024::grid::{Direction, Grid, Position};
+use std::collections::{HashMap, HashSet};
+use std::fmt::Display;
+use std::str::FromStr;
 
 const INPUT: &str = "./input/day21.txt";
 
-#[derive(Debug, Clone, Default, Copy, PartialEq, Eq, Hash)]
-enum NumericButton {
-    #[default]
-    Activate,
-    Button0,
-    Button1,
-    Button2,
-    Button3,
-    Button4,
-    Button5,
-    Button6,
-    Button7,
-    Button8,
-    Button9,
-}
+// Longest sequence worth printing.
+const MAX_SEQUENCE: usize = 1_000_000;
 
-impl NumericButton {
-    fn to(self, other: NumericButton) -> Vec<DirectionalButton> {
-        // A few considerations:
-        // - first keypad:
-        //   - it is better to move in a straight line then to zigzag, so we can press the same
-        //     button repeatedly instead of moving around (at the first directional keypad).
-        //   - reaching the left button is the most expensive, followed by the down button.
-        //   - we always end up at the 'Activate' button at the end.
-        // - second keypad:
-        //   - it is better to move in a straight line than to zigzag (again). For pretty much any
-        //     direction, the order doesn't matter, but... If we need to move left, we may need to
-        //     move up as well; if we do up first, we miss our chance to do a 2 step to the left,
-        //     so we should prefer left first. Otherwise, we end at 'up' then zigzag.
-        //   - I'm less sure about down, but it may be similar.
-        // So, we prefer left, then down, then up, then right. Unless we have to cross the empty
-        // space, at which point we prefer avoiding the zigzag.
-        let start = Position::from(self);
-        let end = Position::from(other);
-        let mut directions = Vec::new();
-
-        match end.x().cmp(&start.x()) {
-            Ordering::Less => directions.extend(std::iter::repeat_n(
-                DirectionalButton::Left,
-                start.x() - end.x(),
-            )),
-            Ordering::Equal => (),
-            Ordering::Greater => directions.extend(std::iter::repeat_n(
-                DirectionalButton::Right,
-                end.x() - start.x(),
-            )),
-        };
+const NUMERIC_KEYPAD: &str = "789\n456\n123\n 0A";
+const DIRECTIONAL_KEYPAD: &str = " ^A\n<v>";
 
-        match end.y().cmp(&start.y()) {
-            Ordering::Less => directions.extend(std::iter::repeat_n(
-                DirectionalButton::Up,
-                start.y() - end.y(),
-            )),
-            Ordering::Equal => (),
-            Ordering::Greater => directions.extend(std::iter::repeat_n(
-                DirectionalButton::Down,
-                end.y() - start.y(),
-            )),
-        };
+const MOVES: [(char, Direction); 4] = [
+    ('<', Direction::Left),
+    ('>', Direction::Right),
+    ('^', Direction::Up),
+    ('v', Direction::Down),
+];
 
-        // We've set up the ordering for the DirectionButton enum.
-        directions.sort_unstable();
+// Blank cells, including those missing at the end of shorter rows, are gaps over which a robot arm
+// must never be.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Keypad(Grid<Option<char>>);
 
-        // If we are dealing with '0' or 'A', and  '1', '4', '7', we need to reverse it to avoid
-        // the blank space.
-        if (self == NumericButton::Button0 || self == NumericButton::Activate)
-            && (other == NumericButton::Button7
-                || other == NumericButton::Button4
-                || other == NumericButton::Button1)
-        {
-            directions.reverse();
-        }
-        if (other == NumericButton::Button0 || other == NumericButton::Activate)
-            && (self == NumericButton::Button7
-                || self == NumericButton::Button4
-                || self == NumericButton::Button1)
-        {
-            directions.reverse();
-        }
+impl Keypad {
+    const ACTIVATE: char = 'A';
 
-        directions
+    fn position(&self, key: char) -> Option<Position> {
+        self.0
+            .iter()
+            .find_map(|(p, &k)| Some(p).filter(|_| k == Some(key)))
     }
-}
 
-impl TryFrom<char> for NumericButton {
-    type Error = char;
-
-    fn try_from(c: char) -> Result<Self, Self::Error> {
-        match c {
-            '0' => Ok(NumericButton::Button0),
-            '1' => Ok(NumericButton::Button1),
-            '2' => Ok(NumericButton::Button2),
-            '3' => Ok(NumericButton::Button3),
-            '4' => Ok(NumericButton::Button4),
-            '5' => Ok(NumericButton::Button5),
-            '6' => Ok(NumericButton::Button6),
-            '7' => Ok(NumericButton::Button7),
-            '8' => Ok(NumericButton::Button8),
-            '9' => Ok(NumericButton::Button9),
-            'A' => Ok(NumericButton::Activate),
-            _ => Err(c),
+    fn key(&self, position: Position) -> Option<char> {
+        self.0.get(position).copied().flatten()
+    }
+
+    // Every way to move from one key to another, without detours and avoiding the gaps.
+    fn move_orders(&self, from: Position, to: Position) -> Vec<String> {
+        fn recursive(
+            keypad: &Keypad,
+            position: Position,
+            end: Position,
+            path: &mut String,
+            paths: &mut Vec<String>,
+        ) {
+            if keypad.key(position).is_none() {
+                return;
+            }
+            if position == end {
+                paths.push(path.clone());
+                return;
+            }
+
+            let needed = [
+                end.x() < position.x(),
+                end.x() > position.x(),
+                end.y() < position.y(),
+                end.y() > position.y(),
+            ];
+            for ((arrow, direction), _) in MOVES.into_iter().zip(needed).filter(|(_, n)| *n) {
+                path.push(arrow);
+                let next = (position + direction).unwrap();
+                recursive(keypad, next, end, path, paths);
+                path.pop();
+            }
         }
+
+        let mut paths = Vec::new();
+        recursive(self, from, to, &mut String::new(), &mut paths);
+        paths
     }
 }
 
-impl From<NumericButton> for Position {
-    fn from(value: NumericButton) -> Position {
-        match value {
-            NumericButton::Button7 => Position::new(0, 0),
-            NumericButton::Button8 => Position::new(1, 0),
-            NumericButton::Button9 => Position::new(2, 0),
-            NumericButton::Button4 => Position::new(0, 1),
-            NumericButton::Button5 => Position::new(1, 1),
-            NumericButton::Button6 => Position::new(2, 1),
-            NumericButton::Button1 => Position::new(0, 2),
-            NumericButton::Button2 => Position::new(1, 2),
-            NumericButton::Button3 => Position::new(2, 2),
-            NumericButton::Button0 => Position::new(1, 3),
-            NumericButton::Activate => Position::new(2, 3),
+impl FromStr for Keypad {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let height = s.lines().count();
+        let width = s.lines().map(|line| line.chars().count()).max();
+        let Some(width) = width.filter(|&w| w > 0) else {
+            return Err("Empty keypad layout".to_string());
+        };
+
+        let mut seen = HashSet::new();
+        let mut keys = Vec::with_capacity(height * width);
+        for line in s.lines() {
+            for c in line.chars() {
+                if c == ' ' {
+                    keys.push(None);
+                } else if seen.insert(c) {
+                    keys.push(Some(c));
+                } else {
+                    return Err(format!("Duplicate key: {c}"));
+                }
+            }
+            keys.extend(std::iter::repeat_n(None, width - line.chars().count()));
         }
+
+        Ok(Self(Grid::new(height, width, keys).unwrap()))
     }
 }
 
-#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
-enum DirectionalButton {
-    Left,
-    Down,
-    Up,
-    Right,
-    #[default]
-    Activate,
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Malfunction {
+    // A robot arm was moved over a gap, or off its keypad, by the given press of the human.
+    Hover { keypad: usize, press: usize },
+    // A key which is not on the keypad, or does not steer a robot.
+    UnknownKey { keypad: usize, key: char },
 }
 
-impl DirectionalButton {
-    fn to(self, other: Self) -> Vec<DirectionalButton> {
+impl Display for Malfunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            DirectionalButton::Left => match other {
-                DirectionalButton::Left => vec![],
-                DirectionalButton::Down => vec![DirectionalButton::Right],
-                DirectionalButton::Up => vec![DirectionalButton::Right, DirectionalButton::Up],
-                DirectionalButton::Right => {
-                    vec![DirectionalButton::Right, DirectionalButton::Right]
-                }
-                DirectionalButton::Activate => vec![
-                    DirectionalButton::Right,
-                    DirectionalButton::Right,
-                    DirectionalButton::Up,
-                ],
-            },
-            DirectionalButton::Down => match other {
-                DirectionalButton::Left => vec![DirectionalButton::Left],
-                DirectionalButton::Down => vec![],
-                DirectionalButton::Up => vec![DirectionalButton::Up],
-                DirectionalButton::Right => vec![DirectionalButton::Right],
-                DirectionalButton::Activate => {
-                    vec![DirectionalButton::Up, DirectionalButton::Right]
-                }
-            },
-            DirectionalButton::Up => match other {
-                DirectionalButton::Left => vec![DirectionalButton::Down, DirectionalButton::Left],
-                DirectionalButton::Down => vec![DirectionalButton::Down],
-                DirectionalButton::Up => vec![],
-                DirectionalButton::Right => vec![DirectionalButton::Down, DirectionalButton::Right],
-                DirectionalButton::Activate => vec![DirectionalButton::Right],
-            },
-            DirectionalButton::Right => match other {
-                DirectionalButton::Left => vec![DirectionalButton::Left, DirectionalButton::Left],
-                DirectionalButton::Down => vec![DirectionalButton::Left, DirectionalButton::Down],
-                DirectionalButton::Up => vec![DirectionalButton::Left, DirectionalButton::Up],
-                DirectionalButton::Right => vec![],
-                DirectionalButton::Activate => vec![DirectionalButton::Up],
-            },
-            DirectionalButton::Activate => match other {
-                DirectionalButton::Left => vec![
-                    DirectionalButton::Down,
-                    DirectionalButton::Left,
-                    DirectionalButton::Left,
-                ],
-                DirectionalButton::Down => vec![DirectionalButton::Left, DirectionalButton::Down],
-                DirectionalButton::Up => vec![DirectionalButton::Left],
-                DirectionalButton::Right => vec![DirectionalButton::Down],
-                DirectionalButton::Activate => vec![],
-            },
+            Malfunction::Hover { keypad, press } => {
+                write!(
+                    f,
+                    "Press {press} moves the arm over keypad {keypad} to a gap"
+                )
+            }
+            Malfunction::UnknownKey { keypad, key } => {
+                write!(f, "Key {key} has no use on keypad {keypad}")
+            }
         }
     }
 }
 
-trait Code {
-    fn shortest_sequence(&self, nbr_intermediaries: usize) -> usize;
-    fn complexity(&self, nbr_intermediaries: usize) -> usize;
+impl std::error::Error for Malfunction {}
+
+// The code is typed on the first keypad, and the human presses the keys of the last one. Every
+// keypad in between is operated by a robot whose arm points at a key of the previous keypad.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct RobotChain {
+    keypads: Vec<Keypad>,
+    // Memoised number of presses from the human, to move from one key to another of a keypad
+    // then press it.
+    costs: HashMap<(usize, char, char), Option<usize>>,
 }
 
-impl<T: AsRef<str> + Debug> Code for T {
-    fn shortest_sequence(&self, nbr_intermediaries: usize) -> usize {
-        // The first robot on a directional keypad can press the same key repeatedly. But, the
-        // robot operating its keypad will always return (thus start) on the `Activate` button.
-        // Thus, the actual order stops mattering at the second robot, and we can keep a running
-        // tally of the transitions between keys (also keeping track of having to go back to the
-        // `Activate` button to actually press it).
-        let s = self
-            .as_ref()
-            .chars()
-            .map(NumericButton::try_from)
-            .collect::<Result<Vec<_>, _>>()
-            .expect("invalid sequence");
-        let mut numeric_button = NumericButton::default();
-        let mut sequence: Counter<(DirectionalButton, DirectionalButton), usize> = Counter::new();
-
-        for digit in s {
-            let mut start = DirectionalButton::default();
-            for d in numeric_button.to(digit).into_iter() {
-                sequence[&(start, d)] += 1;
-                start = d;
-            }
-            // Don't forget to press the activate button to actually press it.
-            sequence[&(start, DirectionalButton::Activate)] += 1;
-            numeric_button = digit;
+impl RobotChain {
+    fn new(keypads: Vec<Keypad>) -> Result<Self, String> {
+        if keypads.is_empty() {
+            return Err("A chain needs at least one keypad".to_string());
+        }
+        if let Some(idx) = keypads
+            .iter()
+            .position(|k| k.position(Keypad::ACTIVATE).is_none())
+        {
+            return Err(format!("Keypad {idx} has no activate button"));
+        }
+        if let Some(idx) = keypads
+            .iter()
+            .skip(1)
+            .position(|k| MOVES.iter().any(|&(arrow, _)| k.position(arrow).is_none()))
+        {
+            return Err(format!("Keypad {} cannot steer a robot", idx + 1));
         }
 
-        for _ in 0..nbr_intermediaries {
-            let mut new_sequence: Counter<(DirectionalButton, DirectionalButton), usize> =
-                Counter::new();
-            for ((start, end), c) in sequence {
-                let mut current = DirectionalButton::default();
-                for d in start.to(end) {
-                    new_sequence[&(current, d)] += c;
-                    current = d;
+        Ok(Self {
+            keypads,
+            costs: HashMap::new(),
+        })
+    }
+
+    // The puzzle's chain: a numeric keypad, then a directional keypad for each intermediary robot
+    // and one for the human.
+    fn standard(nbr_intermediaries: usize) -> Self {
+        let numeric = NUMERIC_KEYPAD.parse().unwrap();
+        let directional = DIRECTIONAL_KEYPAD.parse::<Keypad>().unwrap();
+        let keypads = std::iter::once(numeric)
+            .chain(std::iter::repeat_n(directional, nbr_intermediaries + 1))
+            .collect();
+        Self::new(keypads).unwrap()
+    }
+
+    // Every arm starts, and ends, on the activate button. `None` if a key is missing, or cannot be
+    // reached without a detour.
+    fn sequence_cost(&mut self, level: usize, sequence: &str) -> Option<usize> {
+        if level + 1 == self.keypads.len() {
+            return Some(sequence.chars().count());
+        }
+
+        std::iter::once(Keypad::ACTIVATE)
+            .chain(sequence.chars())
+            .zip(sequence.chars())
+            .map(|(from, to)| self.press_cost(level, from, to))
+            .sum()
+    }
+
+    fn press_cost(&mut self, level: usize, from: char, to: char) -> Option<usize> {
+        if let Some(&cost) = self.costs.get(&(level, from, to)) {
+            return cost;
+        }
+
+        let keypad = &self.keypads[level];
+        let cost = keypad
+            .position(from)
+            .zip(keypad.position(to))
+            .map(|(from, to)| keypad.move_orders(from, to))
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|mut path| {
+                path.push(Keypad::ACTIVATE);
+                self.sequence_cost(level + 1, &path)
+            })
+            .min();
+        self.costs.insert((level, from, to), cost);
+        cost
+    }
+
+    fn code_cost(&mut self, code: &str) -> Option<usize> {
+        self.sequence_cost(0, code)
+    }
+
+    // One of the shortest sequences the human can press. Each move order is picked using the
+    // memoised costs, so only the chosen one is expanded, in time linear in the sequence length.
+    fn sequence(&mut self, level: usize, sequence: &str) -> Option<String> {
+        if level + 1 == self.keypads.len() {
+            return Some(sequence.to_string());
+        }
+
+        std::iter::once(Keypad::ACTIVATE)
+            .chain(sequence.chars())
+            .zip(sequence.chars())
+            .map(|(from, to)| self.press_sequence(level, from, to))
+            .collect()
+    }
+
+    fn press_sequence(&mut self, level: usize, from: char, to: char) -> Option<String> {
+        let keypad = &self.keypads[level];
+        let path = keypad
+            .position(from)
+            .zip(keypad.position(to))
+            .map(|(from, to)| keypad.move_orders(from, to))
+            .unwrap_or_default()
+            .into_iter()
+            .map(|mut path| {
+                path.push(Keypad::ACTIVATE);
+                path
+            })
+            .filter_map(|path| Some((self.sequence_cost(level + 1, &path)?, path)))
+            .min_by_key(|(cost, _)| *cost)
+            .map(|(_, path)| path)?;
+        self.sequence(level + 1, &path)
+    }
+
+    fn code_sequence(&mut self, code: &str) -> Option<String> {
+        self.sequence(0, code)
+    }
+
+    // Replays the human's presses through the chain, returning what was typed on the first keypad.
+    fn simulate(&self, presses: &str) -> Result<String, Malfunction> {
+        let top = self.keypads.len() - 1;
+        let mut arms: Vec<Position> = self.keypads[..top]
+            .iter()
+            .map(|k| k.position(Keypad::ACTIVATE).unwrap())
+            .collect();
+        let mut typed = String::new();
+
+        for (press, mut key) in presses.chars().enumerate() {
+            if self.keypads[top].position(key).is_none() {
+                return Err(Malfunction::UnknownKey { keypad: top, key });
+            }
+
+            // Activations are passed down the chain, until a robot moves or the code is typed.
+            let mut level = top;
+            loop {
+                if level == 0 {
+                    typed.push(key);
+                    break;
                 }
-                // Don't forget to press the activate button to actually press it.
-                new_sequence[&(current, DirectionalButton::Activate)] += c;
+                level -= 1;
+                if key == Keypad::ACTIVATE {
+                    key = self.keypads[level].key(arms[level]).unwrap();
+                    continue;
+                }
+
+                let Some(&(_, direction)) = MOVES.iter().find(|&&(arrow, _)| arrow == key) else {
+                    return Err(Malfunction::UnknownKey {
+                        keypad: level + 1,
+                        key,
+                    });
+                };
+                match (arms[level] + direction).filter(|&p| self.keypads[level].key(p).is_some()) {
+                    Some(arm) => arms[level] = arm,
+                    None => {
+                        return Err(Malfunction::Hover {
+                            keypad: level,
+                            press,
+                        })
+                    }
+                }
+                break;
             }
-            sequence = new_sequence;
         }
 
-        sequence.values().sum()
+        Ok(typed)
     }
+}
 
-    fn complexity(&self, nbr_intermediaries: usize) -> usize {
+trait Code {
+    fn complexity(&self, length: usize) -> usize;
+}
+
+impl<T: AsRef<str>> Code for T {
+    fn complexity(&self, length: usize) -> usize {
         let s = self.as_ref();
-        self.shortest_sequence(nbr_intermediaries)
+        length
             * s.bytes()
                 .filter(u8::is_ascii_digit)
                 .map(|c| usize::from(c - b'0'))
@@ -255,10 +317,43 @@ impl<T: AsRef<str> + Debug> Code for T {
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let input = std::fs::read_to_string(INPUT)?;
 
-    let part1 = input.lines().map(|code| code.complexity(2)).sum::<usize>();
-    println!("The first answer is: {}", part1);
-    let part2 = input.lines().map(|code| code.complexity(25)).sum::<usize>();
-    println!("The second answer is: {}", part2);
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.as_slice() {
+        [] => (),
+        [flag, nbr_intermediaries] if flag == "--sequences" => {
+            let mut chain = RobotChain::standard(nbr_intermediaries.parse()?);
+            for code in input.lines() {
+                let length = chain
+                    .code_cost(code)
+                    .ok_or_else(|| format!("Cannot type code: {code}"))?;
+                if length > MAX_SEQUENCE {
+                    return Err(format!("The sequence for {code} is {length} presses long.").into());
+                }
+                let sequence = chain.code_sequence(code).unwrap();
+                let typed = chain.simulate(&sequence)?;
+                if typed != code {
+                    return Err(format!("{sequence} types {typed} instead of {code}").into());
+                }
+                println!("{code}: {sequence}");
+            }
+        }
+        _ => return Err("Usage: day21 [--sequences NBR_INTERMEDIARIES]".into()),
+    }
+
+    let [part1, part2] = [2, 25].map(|nbr_intermediaries| {
+        let mut chain = RobotChain::standard(nbr_intermediaries);
+        input
+            .lines()
+            .map(|code| {
+                let length = chain
+                    .code_cost(code)
+                    .ok_or_else(|| format!("Cannot type code: {code}"))?;
+                Ok(code.complexity(length))
+            })
+            .sum::<Result<usize, String>>()
+    });
+    println!("The first answer is: {}", part1?);
+    println!("The second answer is: {}", part2?);
 
     Ok(())
 }
@@ -266,6 +361,257 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use counter::Counter;
+    use std::cmp::Ordering;
+
+    #[derive(Debug, Clone, Default, Copy, PartialEq, Eq, Hash)]
+    enum NumericButton {
+        #[default]
+        Activate,
+        Button0,
+        Button1,
+        Button2,
+        Button3,
+        Button4,
+        Button5,
+        Button6,
+        Button7,
+        Button8,
+        Button9,
+    }
+
+    impl NumericButton {
+        fn to(self, other: NumericButton) -> Vec<DirectionalButton> {
+            // A few considerations:
+            // - first keypad:
+            //   - it is better to move in a straight line then to zigzag, so we can press the same
+            //     button repeatedly instead of moving around (at the first directional keypad).
+            //   - reaching the left button is the most expensive, followed by the down button.
+            //   - we always end up at the 'Activate' button at the end.
+            // - second keypad:
+            //   - it is better to move in a straight line than to zigzag (again). For pretty much any
+            //     direction, the order doesn't matter, but... If we need to move left, we may need to
+            //     move up as well; if we do up first, we miss our chance to do a 2 step to the left,
+            //     so we should prefer left first. Otherwise, we end at 'up' then zigzag.
+            //   - I'm less sure about down, but it may be similar.
+            // So, we prefer left, then down, then up, then right. Unless we have to cross the empty
+            // space, at which point we prefer avoiding the zigzag.
+            let start = Position::from(self);
+            let end = Position::from(other);
+            let mut directions = Vec::new();
+
+            match end.x().cmp(&start.x()) {
+                Ordering::Less => directions.extend(std::iter::repeat_n(
+                    DirectionalButton::Left,
+                    start.x() - end.x(),
+                )),
+                Ordering::Equal => (),
+                Ordering::Greater => directions.extend(std::iter::repeat_n(
+                    DirectionalButton::Right,
+                    end.x() - start.x(),
+                )),
+            };
+
+            match end.y().cmp(&start.y()) {
+                Ordering::Less => directions.extend(std::iter::repeat_n(
+                    DirectionalButton::Up,
+                    start.y() - end.y(),
+                )),
+                Ordering::Equal => (),
+                Ordering::Greater => directions.extend(std::iter::repeat_n(
+                    DirectionalButton::Down,
+                    end.y() - start.y(),
+                )),
+            };
+
+            // We've set up the ordering for the DirectionButton enum.
+            directions.sort_unstable();
+
+            // If we are dealing with '0' or 'A', and  '1', '4', '7', we need to reverse it to avoid
+            // the blank space.
+            if (self == NumericButton::Button0 || self == NumericButton::Activate)
+                && (other == NumericButton::Button7
+                    || other == NumericButton::Button4
+                    || other == NumericButton::Button1)
+            {
+                directions.reverse();
+            }
+            if (other == NumericButton::Button0 || other == NumericButton::Activate)
+                && (self == NumericButton::Button7
+                    || self == NumericButton::Button4
+                    || self == NumericButton::Button1)
+            {
+                directions.reverse();
+            }
+
+            directions
+        }
+    }
+
+    impl TryFrom<char> for NumericButton {
+        type Error = char;
+
+        fn try_from(c: char) -> Result<Self, Self::Error> {
+            match c {
+                '0' => Ok(NumericButton::Button0),
+                '1' => Ok(NumericButton::Button1),
+                '2' => Ok(NumericButton::Button2),
+                '3' => Ok(NumericButton::Button3),
+                '4' => Ok(NumericButton::Button4),
+                '5' => Ok(NumericButton::Button5),
+                '6' => Ok(NumericButton::Button6),
+                '7' => Ok(NumericButton::Button7),
+                '8' => Ok(NumericButton::Button8),
+                '9' => Ok(NumericButton::Button9),
+                'A' => Ok(NumericButton::Activate),
+                _ => Err(c),
+            }
+        }
+    }
+
+    impl From<NumericButton> for Position {
+        fn from(value: NumericButton) -> Position {
+            match value {
+                NumericButton::Button7 => Position::new(0, 0),
+                NumericButton::Button8 => Position::new(1, 0),
+                NumericButton::Button9 => Position::new(2, 0),
+                NumericButton::Button4 => Position::new(0, 1),
+                NumericButton::Button5 => Position::new(1, 1),
+                NumericButton::Button6 => Position::new(2, 1),
+                NumericButton::Button1 => Position::new(0, 2),
+                NumericButton::Button2 => Position::new(1, 2),
+                NumericButton::Button3 => Position::new(2, 2),
+                NumericButton::Button0 => Position::new(1, 3),
+                NumericButton::Activate => Position::new(2, 3),
+            }
+        }
+    }
+
+    #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+    enum DirectionalButton {
+        Left,
+        Down,
+        Up,
+        Right,
+        #[default]
+        Activate,
+    }
+
+    impl DirectionalButton {
+        fn to(self, other: Self) -> Vec<DirectionalButton> {
+            match self {
+                DirectionalButton::Left => match other {
+                    DirectionalButton::Left => vec![],
+                    DirectionalButton::Down => vec![DirectionalButton::Right],
+                    DirectionalButton::Up => vec![DirectionalButton::Right, DirectionalButton::Up],
+                    DirectionalButton::Right => {
+                        vec![DirectionalButton::Right, DirectionalButton::Right]
+                    }
+                    DirectionalButton::Activate => vec![
+                        DirectionalButton::Right,
+                        DirectionalButton::Right,
+                        DirectionalButton::Up,
+                    ],
+                },
+                DirectionalButton::Down => match other {
+                    DirectionalButton::Left => vec![DirectionalButton::Left],
+                    DirectionalButton::Down => vec![],
+                    DirectionalButton::Up => vec![DirectionalButton::Up],
+                    DirectionalButton::Right => vec![DirectionalButton::Right],
+                    DirectionalButton::Activate => {
+                        vec![DirectionalButton::Up, DirectionalButton::Right]
+                    }
+                },
+                DirectionalButton::Up => match other {
+                    DirectionalButton::Left => {
+                        vec![DirectionalButton::Down, DirectionalButton::Left]
+                    }
+                    DirectionalButton::Down => vec![DirectionalButton::Down],
+                    DirectionalButton::Up => vec![],
+                    DirectionalButton::Right => {
+                        vec![DirectionalButton::Down, DirectionalButton::Right]
+                    }
+                    DirectionalButton::Activate => vec![DirectionalButton::Right],
+                },
+                DirectionalButton::Right => match other {
+                    DirectionalButton::Left => {
+                        vec![DirectionalButton::Left, DirectionalButton::Left]
+                    }
+                    DirectionalButton::Down => {
+                        vec![DirectionalButton::Left, DirectionalButton::Down]
+                    }
+                    DirectionalButton::Up => vec![DirectionalButton::Left, DirectionalButton::Up],
+                    DirectionalButton::Right => vec![],
+                    DirectionalButton::Activate => vec![DirectionalButton::Up],
+                },
+                DirectionalButton::Activate => match other {
+                    DirectionalButton::Left => vec![
+                        DirectionalButton::Down,
+                        DirectionalButton::Left,
+                        DirectionalButton::Left,
+                    ],
+                    DirectionalButton::Down => {
+                        vec![DirectionalButton::Left, DirectionalButton::Down]
+                    }
+                    DirectionalButton::Up => vec![DirectionalButton::Left],
+                    DirectionalButton::Right => vec![DirectionalButton::Down],
+                    DirectionalButton::Activate => vec![],
+                },
+            }
+        }
+    }
+
+    // The original, hand-tuned, move orders, which the search must agree with.
+    trait Heuristic {
+        fn shortest_sequence(&self, nbr_intermediaries: usize) -> usize;
+    }
+
+    impl<T: AsRef<str>> Heuristic for T {
+        fn shortest_sequence(&self, nbr_intermediaries: usize) -> usize {
+            // The first robot on a directional keypad can press the same key repeatedly. But, the
+            // robot operating its keypad will always return (thus start) on the `Activate` button.
+            // Thus, the actual order stops mattering at the second robot, and we can keep a running
+            // tally of the transitions between keys (also keeping track of having to go back to the
+            // `Activate` button to actually press it).
+            let s = self
+                .as_ref()
+                .chars()
+                .map(NumericButton::try_from)
+                .collect::<Result<Vec<_>, _>>()
+                .expect("invalid sequence");
+            let mut numeric_button = NumericButton::default();
+            let mut sequence: Counter<(DirectionalButton, DirectionalButton), usize> =
+                Counter::new();
+
+            for digit in s {
+                let mut start = DirectionalButton::default();
+                for d in numeric_button.to(digit).into_iter() {
+                    sequence[&(start, d)] += 1;
+                    start = d;
+                }
+                // Don't forget to press the activate button to actually press it.
+                sequence[&(start, DirectionalButton::Activate)] += 1;
+                numeric_button = digit;
+            }
+
+            for _ in 0..nbr_intermediaries {
+                let mut new_sequence: Counter<(DirectionalButton, DirectionalButton), usize> =
+                    Counter::new();
+                for ((start, end), c) in sequence {
+                    let mut current = DirectionalButton::default();
+                    for d in start.to(end) {
+                        new_sequence[&(current, d)] += c;
+                        current = d;
+                    }
+                    // Don't forget to press the activate button to actually press it.
+                    new_sequence[&(current, DirectionalButton::Activate)] += c;
+                }
+                sequence = new_sequence;
+            }
+
+            sequence.values().sum()
+        }
+    }
 
     const EXAMPLE: &str = "\
         029A\n\
@@ -277,13 +623,130 @@ mod tests {
 
     #[test]
     fn test_part1() {
-        let codes = EXAMPLE.lines();
-        let actual = codes
-            .into_iter()
-            .map(|code| code.complexity(2))
+        let mut chain = RobotChain::standard(2);
+        let actual = EXAMPLE
+            .lines()
+            .map(|code| code.complexity(chain.code_cost(code).unwrap()))
             .sum::<usize>();
         let expected = 126384;
 
         assert_eq!(expected, actual);
     }
+
+    #[test]
+    fn searched_lengths() {
+        let mut chain = RobotChain::standard(2);
+        let actual: Vec<Option<usize>> =
+            EXAMPLE.lines().map(|code| chain.code_cost(code)).collect();
+        let expected = vec![Some(68), Some(60), Some(68), Some(64), Some(64)];
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn search_agrees_with_heuristic() {
+        for nbr_intermediaries in 0..=25 {
+            let mut chain = RobotChain::standard(nbr_intermediaries);
+            for code in EXAMPLE.lines() {
+                assert_eq!(
+                    Some(code.shortest_sequence(nbr_intermediaries)),
+                    chain.code_cost(code),
+                    "{code} with {nbr_intermediaries} intermediaries"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn keypad_layout() {
+        let keypad = NUMERIC_KEYPAD.parse::<Keypad>().unwrap();
+        assert_eq!(Some(Position::new(1, 3)), keypad.position('0'));
+        assert_eq!(None, keypad.key(Position::new(0, 3)));
+        assert_eq!(
+            vec!["^<".to_string()],
+            keypad.move_orders(Position::new(1, 3), Position::new(0, 2))
+        );
+
+        let keypad = "A\n12".parse::<Keypad>().unwrap();
+        assert_eq!(None, keypad.key(Position::new(1, 0)));
+        assert_eq!(Err("Duplicate key: 1".to_string()), "11A".parse::<Keypad>());
+        assert_eq!(Err("Empty keypad layout".to_string()), "".parse::<Keypad>());
+    }
+
+    #[test]
+    fn custom_chains() {
+        let row = "<^v>A".parse::<Keypad>().unwrap();
+        let directional = DIRECTIONAL_KEYPAD.parse::<Keypad>().unwrap();
+
+        // The human types directly on the only keypad.
+        let mut chain = RobotChain::new(vec![row.clone()]).unwrap();
+        assert_eq!(Some(2), chain.code_cost("<A"));
+
+        // "<<<<A>>>>A" on the row keypad.
+        let mut chain = RobotChain::new(vec![row.clone(), row.clone()]).unwrap();
+        assert_eq!(Some(10), chain.code_cost("<A"));
+
+        // "v<<AAAA>>^A" then "vAAAA^A" on the usual directional keypad.
+        let keypads = vec![row.clone(), directional.clone(), directional.clone()];
+        let mut chain = RobotChain::new(keypads).unwrap();
+        assert_eq!(Some(18), chain.code_cost("<A"));
+
+        // The only way to '1' goes over the gap.
+        let mut chain = RobotChain::new(vec!["A 1".parse().unwrap(), directional]).unwrap();
+        assert_eq!(None, chain.code_cost("1"));
+        assert_eq!(None, chain.code_cost("2"));
+
+        let numeric = NUMERIC_KEYPAD.parse::<Keypad>().unwrap();
+        assert!(RobotChain::new(vec![row.clone(), numeric]).is_err());
+        assert!(RobotChain::new(vec![]).is_err());
+    }
+
+    #[test]
+    fn example_sequences() {
+        let mut chain = RobotChain::standard(2);
+        for code in EXAMPLE.lines() {
+            let sequence = chain.code_sequence(code).unwrap();
+
+            assert_eq!(code.shortest_sequence(2), sequence.len());
+            assert_eq!(Ok(code.to_string()), chain.simulate(&sequence));
+        }
+
+        // Longer chains only expand the chosen move orders.
+        let mut chain = RobotChain::standard(8);
+        for code in EXAMPLE.lines() {
+            let sequence = chain.code_sequence(code).unwrap();
+
+            assert_eq!(chain.code_cost(code), Some(sequence.len()));
+            assert_eq!(Ok(code.to_string()), chain.simulate(&sequence));
+        }
+
+        // The puzzle lists every shortest way to type the first code.
+        let mut chain = RobotChain::standard(0);
+        let sequence = chain.code_sequence("029A").unwrap();
+        assert!(["<A^A>^^AvvvA", "<A^A^>^AvvvA", "<A^A^^>AvvvA"].contains(&sequence.as_str()));
+    }
+
+    #[test]
+    fn simulation() {
+        let chain = RobotChain::standard(2);
+        let presses = "<vA<AA>>^AvAA<^A>A<v<A>>^AvA^A<vA>^A<v<A>^A>AAvA^A<v<A>A>^AAAvA<^A>A";
+        assert_eq!(Ok("029A".to_string()), chain.simulate(presses));
+
+        let chain = RobotChain::standard(0);
+        assert_eq!(Ok("0".to_string()), chain.simulate("<A"));
+        assert_eq!(
+            Err(Malfunction::Hover {
+                keypad: 0,
+                press: 1
+            }),
+            chain.simulate("<<A")
+        );
+        assert_eq!(
+            Err(Malfunction::UnknownKey {
+                keypad: 1,
+                key: '0'
+            }),
+            chain.simulate("0")
+        );
+    }
 }
@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::cmp::Reverse;
 use std::hash::Hash;
 
 const INPUT: &str = "./input/day22.txt";
@@ -8,6 +8,51 @@ const RIGHT_SHIFT: u32 = 5;
 const MULTIPLIER: u32 = 11;
 const MODULO: u64 = 16777216;
 
+const BITS: u32 = MODULO.trailing_zeros();
+
+// Mixing in a shifted value is linear over GF(2), so a number of steps is a 24x24 bit matrix. It
+// is stored as the image of each bit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct BitMatrix([u64; BITS as usize]);
+
+impl BitMatrix {
+    fn from_fn(f: impl Fn(u64) -> u64) -> Self {
+        Self(std::array::from_fn(|bit| f(1 << bit)))
+    }
+
+    fn apply(&self, value: u64) -> u64 {
+        self.0
+            .iter()
+            .enumerate()
+            .filter(|&(bit, _)| value >> bit & 1 == 1)
+            .fold(0, |acc, (_, image)| acc ^ image)
+    }
+
+    fn pow(self, mut exponent: u64) -> Self {
+        let mut result = Self::from_fn(|value| value);
+        let mut square = self;
+        while exponent > 0 {
+            if exponent & 1 == 1 {
+                result = Self::from_fn(|value| square.apply(result.apply(value)));
+            }
+            square = Self::from_fn(|value| square.apply(square.apply(value)));
+            exponent >>= 1;
+        }
+        result
+    }
+}
+
+// Undoes `value ^ value << shift`, the lowest bits being right already, and `shift` more of them
+// being right after each round.
+fn unmix_left(value: u64, shift: u32) -> u64 {
+    (0..BITS.div_ceil(shift)).fold(value, |acc, _| (value ^ acc << shift) % MODULO)
+}
+
+// Same as above, from the highest bits.
+fn unmix_right(value: u64, shift: u32) -> u64 {
+    (0..BITS.div_ceil(shift)).fold(value, |acc, _| value ^ acc >> shift)
+}
+
 #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
 struct SecretNumbers(u64);
 
@@ -15,6 +60,60 @@ impl SecretNumbers {
     fn new(initial: u64) -> Self {
         Self(initial)
     }
+
+    fn step(secret: u64) -> u64 {
+        let secret = (secret ^ secret << LEFT_SHIFT) % MODULO;
+        let secret = (secret ^ secret >> RIGHT_SHIFT) % MODULO;
+        (secret ^ secret << MULTIPLIER) % MODULO
+    }
+
+    fn previous(self) -> Self {
+        let secret = unmix_left(self.0 % MODULO, MULTIPLIER);
+        let secret = unmix_right(secret, RIGHT_SHIFT);
+        Self(unmix_left(secret, LEFT_SHIFT))
+    }
+
+    fn skip_ahead(self, n: u64) -> Self {
+        match n {
+            0 => self,
+            _ => Self(BitMatrix::from_fn(Self::step).pow(n).apply(self.0 % MODULO)),
+        }
+    }
+
+    fn skip_back(self, n: u64) -> Self {
+        let previous = BitMatrix::from_fn(|secret| Self(secret).previous().0);
+        Self(previous.pow(n).apply(self.0 % MODULO))
+    }
+
+    // The steps are a permutation of the 24 bit numbers, so every secret comes back eventually.
+    // Non-zero secrets should be on a cycle whose length divides 2^24 - 1, in which case only its
+    // divisors need checking. Otherwise, the cycle is walked.
+    fn cycle_length(self) -> u64 {
+        let start = self.skip_ahead(1);
+        let mut length = MODULO - 1;
+        if start.skip_ahead(length) != start {
+            return std::iter::successors(Some(start.0), |&secret| Some(Self::step(secret)))
+                .skip(1)
+                .position(|secret| secret == start.0)
+                .unwrap() as u64
+                + 1;
+        }
+
+        let mut remaining = length;
+        let mut factor = 2;
+        while remaining > 1 {
+            if remaining.is_multiple_of(factor) {
+                while remaining.is_multiple_of(factor) {
+                    remaining /= factor;
+                }
+                while length.is_multiple_of(factor) && start.skip_ahead(length / factor) == start {
+                    length /= factor;
+                }
+            }
+            factor += 1;
+        }
+        length
+    }
 }
 
 impl Iterator for SecretNumbers {
@@ -22,34 +121,121 @@ impl Iterator for SecretNumbers {
 
     fn next(&mut self) -> Option<Self::Item> {
         let current = self.0;
-        self.0 = (self.0 ^ self.0 << LEFT_SHIFT) % MODULO;
-        self.0 = (self.0 ^ self.0 >> RIGHT_SHIFT) % MODULO;
-        self.0 = (self.0 ^ self.0 << MULTIPLIER) % MODULO;
+        self.0 = Self::step(self.0);
         Some(current)
     }
 }
 
-fn part2(buyers: &[SecretNumbers]) -> u64 {
-    let mut counter: HashMap<[i32; 4], u64> = HashMap::new();
-    for buyer in buyers {
-        let mut current: HashMap<[i32; 4], u64> = HashMap::new();
-        // Take 2001 prices, not 2000, because we need the initial one for the changes.
-        let prices: Vec<u64> = (*buyer).take(2001).map(|n| n % 10).collect();
-        let changes = prices
-            .windows(2)
-            .map(|p| p[1] as i32 - p[0] as i32)
-            .collect::<Vec<_>>();
-        for (c, p) in changes.windows(4).zip(prices.iter().skip(4)) {
-            // The monkey can only sell once, the first time he sees the changes.
-            current.entry(c.try_into().unwrap()).or_insert(*p);
-        }
+// Changes are within -9..=9, so a sequence of four of them is a base 19 number.
+const CHANGES: usize = 19;
+const SEQUENCES: usize = CHANGES * CHANGES * CHANGES * CHANGES;
 
-        for (c, p) in current {
-            *counter.entry(c).or_default() += p;
+type Sequence = [i8; 4];
+
+fn decode(mut index: usize) -> Sequence {
+    let mut sequence = [0; 4];
+    for change in sequence.iter_mut().rev() {
+        *change = (index % CHANGES) as i8 - 9;
+        index /= CHANGES;
+    }
+    sequence
+}
+
+fn format_sequence(sequence: Sequence) -> String {
+    sequence.map(|change| change.to_string()).join(",")
+}
+
+// Every (encoded) sequence of four changes along with the price after it, in order.
+fn offers(buyer: SecretNumbers) -> impl Iterator<Item = (usize, u64)> {
+    // Take 2001 prices, not 2000, because we need the initial one for the changes.
+    let mut sequence = 0;
+    buyer
+        .take(2001)
+        .map(|n| n % 10)
+        .enumerate()
+        .scan(None, move |previous, (idx, price)| {
+            if let Some(previous) = *previous {
+                sequence = (sequence * CHANGES + (price + 9 - previous) as usize) % SEQUENCES;
+            }
+            *previous = Some(price);
+            Some((idx >= 4).then_some((sequence, price)))
+        })
+        .flatten()
+}
+
+// Adds the price the buyer sells at to the total of each sequence of changes. The monkey only
+// sells the first time he sees a sequence, which `seen` keeps track of as a bitmap.
+fn sell(buyer: SecretNumbers, totals: &mut [u64], seen: &mut [u64]) {
+    seen.fill(0);
+    for (sequence, price) in offers(buyer) {
+        let (word, bit) = (sequence / 64, 1 << (sequence % 64));
+        if seen[word] & bit == 0 {
+            seen[word] |= bit;
+            totals[sequence] += price;
         }
     }
+}
 
-    counter.into_values().max().unwrap()
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Market {
+    sequence: Sequence,
+    bananas: u64,
+    // The price each buyer sold at, if the sequence showed up at all.
+    prices: Vec<Option<u64>>,
+    // Ordered by decreasing bananas, then by sequence, so ties are easy to spot.
+    top: Vec<(Sequence, u64)>,
+}
+
+fn part2(buyers: &[SecretNumbers], top: usize) -> Market {
+    let threads = std::thread::available_parallelism().map_or(1, usize::from);
+    let chunk_size = buyers.len().div_ceil(threads).max(1);
+
+    let totals = std::thread::scope(|scope| {
+        let handles: Vec<_> = buyers
+            .chunks(chunk_size)
+            .map(|chunk| {
+                scope.spawn(move || {
+                    let mut totals = vec![0; SEQUENCES];
+                    let mut seen = vec![0; SEQUENCES.div_ceil(64)];
+                    for &buyer in chunk {
+                        sell(buyer, &mut totals, &mut seen);
+                    }
+                    totals
+                })
+            })
+            .collect();
+
+        handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .fold(vec![0; SEQUENCES], |mut acc, totals| {
+                acc.iter_mut().zip(totals).for_each(|(a, t)| *a += t);
+                acc
+            })
+    });
+
+    let mut ranking: Vec<(usize, u64)> = totals.into_iter().enumerate().collect();
+    ranking.sort_unstable_by_key(|&(sequence, bananas)| (Reverse(bananas), sequence));
+    let (best, bananas) = ranking[0];
+    let prices = buyers
+        .iter()
+        .map(|&buyer| {
+            offers(buyer)
+                .find(|&(sequence, _)| sequence == best)
+                .map(|(_, price)| price)
+        })
+        .collect();
+
+    Market {
+        sequence: decode(best),
+        bananas,
+        prices,
+        top: ranking
+            .into_iter()
+            .take(top)
+            .map(|(sequence, bananas)| (decode(sequence), bananas))
+            .collect(),
+    }
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -59,6 +245,41 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         .map(|line| SecretNumbers::new(line.parse().unwrap()))
         .collect();
 
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.as_slice() {
+        [] => (),
+        [flag, n] if flag == "--nth" => {
+            // Negative values go back in time.
+            let n: i64 = n.parse()?;
+            for buyer in &buyers {
+                let secret = match n {
+                    0.. => buyer.skip_ahead(n.unsigned_abs()),
+                    _ => buyer.skip_back(n.unsigned_abs()),
+                };
+                println!(
+                    "{}: {} (cycle of {})",
+                    buyer.0,
+                    secret.0,
+                    buyer.cycle_length()
+                );
+            }
+        }
+        [flag, k] if flag == "--top" => {
+            let market = part2(&buyers, k.parse()?);
+            for (sequence, bananas) in &market.top {
+                println!("{}: {bananas}", format_sequence(*sequence));
+            }
+            println!("Prices for {}:", format_sequence(market.sequence));
+            for (buyer, price) in buyers.iter().zip(&market.prices) {
+                match price {
+                    Some(price) => println!("{}: {price}", buyer.0),
+                    None => println!("{}: no sale", buyer.0),
+                }
+            }
+        }
+        _ => return Err("Usage: day22 [--nth N | --top K]".into()),
+    }
+
     println!(
         "The first answer is: {}",
         buyers
@@ -66,7 +287,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             .filter_map(|buyer| buyer.clone().nth(2000))
             .sum::<u64>()
     );
-    println!("The second answer is: {}", part2(&buyers));
+    println!("The second answer is: {}", part2(&buyers, 1).bananas);
 
     Ok(())
 }
@@ -74,6 +295,31 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::collections::HashMap;
+
+    // The straightforward implementation, to check the faster one against.
+    fn reference_part2(buyers: &[SecretNumbers]) -> u64 {
+        let mut counter: HashMap<[i32; 4], u64> = HashMap::new();
+        for buyer in buyers {
+            let mut current: HashMap<[i32; 4], u64> = HashMap::new();
+            // Take 2001 prices, not 2000, because we need the initial one for the changes.
+            let prices: Vec<u64> = (*buyer).take(2001).map(|n| n % 10).collect();
+            let changes = prices
+                .windows(2)
+                .map(|p| p[1] as i32 - p[0] as i32)
+                .collect::<Vec<_>>();
+            for (c, p) in changes.windows(4).zip(prices.iter().skip(4)) {
+                // The monkey can only sell once, the first time he sees the changes.
+                current.entry(c.try_into().unwrap()).or_insert(*p);
+            }
+
+            for (c, p) in current {
+                *counter.entry(c).or_default() += p;
+            }
+        }
+
+        counter.into_values().max().unwrap()
+    }
 
     const EXAMPLE: &str = "\
     1\n\
@@ -113,9 +359,79 @@ mod tests {
             SecretNumbers::new(3),
             SecretNumbers::new(2024),
         ];
-        let actual = part2(&buyers);
-        let expected: u64 = 23;
+        let actual = part2(&buyers, 3);
+
+        assert_eq!(23, actual.bananas);
+        assert_eq!([-2, 1, -1, 3], actual.sequence);
+        assert_eq!("-2,1,-1,3", format_sequence(actual.sequence));
+        assert_eq!(vec![Some(7), Some(7), None, Some(9)], actual.prices);
+        assert_eq!(3, actual.top.len());
+        assert_eq!(([-2, 1, -1, 3], 23), actual.top[0]);
+        assert!(actual.top.windows(2).all(|w| w[0].1 >= w[1].1));
+    }
 
+    #[test]
+    fn sequence_decoding() {
+        assert_eq!([-9, -9, -9, -9], decode(0));
+        assert_eq!([-9, -9, -9, -8], decode(1));
+        assert_eq!([-8, -9, -9, -9], decode(CHANGES * CHANGES * CHANGES));
+        assert_eq!([9, 9, 9, 9], decode(SEQUENCES - 1));
+    }
+
+    #[test]
+    fn matches_reference() {
+        let buyers: Vec<SecretNumbers> = (1..=64)
+            .chain([2024, 16777215])
+            .map(SecretNumbers::new)
+            .collect();
+
+        for count in [1, 4, 17, buyers.len()] {
+            assert_eq!(
+                reference_part2(&buyers[..count]),
+                part2(&buyers[..count], 1).bananas
+            );
+        }
+    }
+
+    #[test]
+    fn jumps() {
+        let secret = SecretNumbers::new(123);
+        let expected: Vec<u64> = secret.take(11).collect();
+        let actual: Vec<u64> = (0..11).map(|n| secret.skip_ahead(n).0).collect();
         assert_eq!(expected, actual);
+
+        for initial in [1, 10, 100, 2024] {
+            let secret = SecretNumbers::new(initial);
+            assert_eq!(secret.clone().nth(2000), Some(secret.skip_ahead(2000).0));
+            assert_eq!(secret, secret.skip_ahead(2000).skip_back(2000));
+        }
+    }
+
+    #[test]
+    fn previous() {
+        let secret = SecretNumbers::new(15887950);
+        assert_eq!(SecretNumbers::new(123), secret.previous());
+        assert_eq!(
+            SecretNumbers::new(5908254),
+            SecretNumbers::new(123).skip_ahead(10)
+        );
+        assert_eq!(
+            SecretNumbers::new(123),
+            SecretNumbers::new(5908254).skip_back(10)
+        );
+
+        let secret = SecretNumbers::new(16777215);
+        assert_eq!(secret, secret.skip_ahead(1).previous());
+    }
+
+    #[test]
+    fn cycle_length() {
+        assert_eq!(1, SecretNumbers::new(0).cycle_length());
+        let length = SecretNumbers::new(123).cycle_length();
+        assert_eq!(16777215, length);
+
+        let n = 1_000_000_000_000;
+        let secret = SecretNumbers::new(123);
+        assert_eq!(secret.skip_ahead(n % length), secret.skip_ahead(n));
     }
 }
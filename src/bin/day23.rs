@@ -1,80 +1,55 @@
-use std::collections::{HashMap, HashSet};
+use advent_of_code_2024::graph::Graph;
 
 const INPUT: &str = "./input/day23.txt";
 
-fn parse_input(input: &str) -> HashMap<&str, HashSet<&str>> {
-    input.lines().fold(HashMap::new(), |mut acc, line| {
-        let (a, b) = line.split_once('-').unwrap();
-        acc.entry(a).or_default().insert(b);
-        acc.entry(b).or_default().insert(a);
-        acc
-    })
+fn parse_input(input: &str) -> Graph {
+    Graph::from_edges(input.lines().map(|line| line.split_once('-').unwrap()))
 }
 
-fn part1(connections: &HashMap<&str, HashSet<&str>>) -> usize {
-    connections
-        .iter()
-        .flat_map(|(key, values)| {
-            values.iter().flat_map(move |value| {
-                if value > key {
-                    connections.get(value).map(move |thirds| {
-                        thirds.intersection(values).filter_map(move |third| {
-                            if third > value {
-                                Some((key, value, third))
-                            } else {
-                                None
-                            }
-                        })
-                    })
-                } else {
-                    None
-                }
-            })
+fn part1(network: &Graph) -> usize {
+    network
+        .triangles()
+        .into_iter()
+        .filter(|triangle| {
+            triangle
+                .iter()
+                .any(|&computer| network.name(computer).starts_with('t'))
         })
-        .flatten()
-        .filter(|(a, b, c)| a.starts_with('t') || b.starts_with('t') || c.starts_with('t'))
         .count()
 }
 
-fn part2(connections: &HashMap<&str, HashSet<&str>>) -> String {
-    // This is simply a matter of finding the maximal clique. We'll use a greedy algorithm.
-    connections
-        .iter()
-        .map(|(key, values)| {
-            let mut clique = HashSet::new();
-            clique.insert(*key);
-            let mut to_visit = values.iter().collect::<Vec<&&str>>();
-            let mut visited = HashSet::new();
-            visited.insert(key);
-            while let Some(computer) = to_visit.pop() {
-                if visited.insert(computer) {
-                    // Add a new computer if it has connections to all the already connected
-                    // computers.
-                    if let Some(new_connections) = connections.get(computer) {
-                        if new_connections.is_superset(&clique) {
-                            clique.insert(computer);
-                            to_visit.extend(new_connections);
-                        }
-                    }
-                }
-            }
-            clique
-        })
-        .max_by_key(HashSet::len)
+// The largest groups of interconnected computers, by name.
+fn maximum_cliques(network: &Graph) -> Vec<Vec<&str>> {
+    let mut cliques: Vec<Vec<&str>> = network
+        .maximum_cliques()
+        .into_iter()
         .map(|clique| {
-            let mut clique = clique.into_iter().collect::<Vec<&str>>();
-            clique.sort();
-            clique.join(",")
+            let mut clique: Vec<&str> = clique.into_iter().map(|id| network.name(id)).collect();
+            clique.sort_unstable();
+            clique
         })
-        .unwrap()
+        .collect();
+    cliques.sort_unstable();
+    cliques
+}
+
+fn password(clique: &[&str]) -> String {
+    clique.join(",")
+}
+
+fn part2(network: &Graph) -> String {
+    maximum_cliques(network)
+        .first()
+        .map(|clique| password(clique))
+        .unwrap_or_default()
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let input = std::fs::read_to_string(INPUT)?;
-    let connections = parse_input(&input);
+    let network = parse_input(&input);
 
-    println!("The first answer is: {}", part1(&connections));
-    println!("The first answer is: {}", part2(&connections));
+    println!("The first answer is: {}", part1(&network));
+    println!("The second answer is: {}", part2(&network));
 
     Ok(())
 }
@@ -82,6 +57,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::collections::{BTreeMap, BTreeSet};
 
     const EXAMPLE: &str = "\
         kh-tc\n\
@@ -120,8 +96,8 @@ mod tests {
 
     #[test]
     fn test_part1() {
-        let connections = parse_input(EXAMPLE);
-        let actual = part1(&connections);
+        let network = parse_input(EXAMPLE);
+        let actual = part1(&network);
         let expected = 7;
 
         assert_eq!(expected, actual);
@@ -129,10 +105,101 @@ mod tests {
 
     #[test]
     fn test_part2() {
-        let connections = parse_input(EXAMPLE);
-        let actual = part2(&connections);
+        let network = parse_input(EXAMPLE);
+        let actual = part2(&network);
         let expected = "co,de,ka,ta";
 
         assert_eq!(expected, actual);
+        assert_eq!(
+            vec![vec!["co", "de", "ka", "ta"]],
+            maximum_cliques(&network)
+        );
+    }
+
+    // The original solution, growing a clique greedily from every computer. The neighbours are
+    // kept sorted by name so that the visit order is fixed: the last one is tried first.
+    fn greedy_part2(input: &str) -> String {
+        let connections: BTreeMap<&str, BTreeSet<&str>> =
+            input.lines().fold(BTreeMap::new(), |mut acc, line| {
+                let (a, b) = line.split_once('-').unwrap();
+                acc.entry(a).or_default().insert(b);
+                acc.entry(b).or_default().insert(a);
+                acc
+            });
+
+        connections
+            .iter()
+            .map(|(key, values)| {
+                let mut clique = BTreeSet::from([*key]);
+                let mut to_visit = values.iter().copied().collect::<Vec<&str>>();
+                let mut visited = BTreeSet::from([*key]);
+                while let Some(computer) = to_visit.pop() {
+                    let new_connections = &connections[computer];
+                    if visited.insert(computer) && new_connections.is_superset(&clique) {
+                        clique.insert(computer);
+                        to_visit.extend(new_connections);
+                    }
+                }
+                clique
+            })
+            .max_by_key(BTreeSet::len)
+            .map(|clique| {
+                let mut clique = clique.into_iter().collect::<Vec<&str>>();
+                clique.sort();
+                clique.join(",")
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn greedy_growth_fails() {
+        // A group of eight computers and, for each pair of them, a decoy connected to the other
+        // six. A decoy and six of the group only make a group of seven. The decoys' names sort
+        // after the group's, so greedy growth from a computer of the group always adds one of its
+        // decoys first, which then keeps out two of the group.
+        let group: Vec<String> = (0..8).map(|idx| format!("k{idx}")).collect();
+        let mut input = String::new();
+        for (idx, a) in group.iter().enumerate() {
+            for b in &group[idx + 1..] {
+                input.push_str(&format!("{a}-{b}\n"));
+            }
+        }
+        for left_out in 0..8 {
+            for other in left_out + 1..8 {
+                for (idx, member) in group.iter().enumerate() {
+                    if idx != left_out && idx != other {
+                        input.push_str(&format!("x{left_out}{other}-{member}\n"));
+                    }
+                }
+            }
+        }
+
+        let network = parse_input(&input);
+        let expected = group.join(",");
+        assert_eq!(
+            vec![group.iter().map(String::as_str).collect::<Vec<_>>()],
+            maximum_cliques(&network)
+        );
+        assert_eq!(expected, part2(&network));
+        assert_eq!(7, greedy_part2(&input).split(',').count());
+    }
+
+    #[test]
+    fn several_maximum_cliques() {
+        // Three overlapping groups of four, the password being that of the first one.
+        let network = parse_input(
+            "aa-bb\naa-cc\naa-dd\nbb-cc\nbb-dd\ncc-dd\n\
+             cc-ee\ncc-ff\ndd-ee\ndd-ff\nee-ff\naa-ee\n",
+        );
+
+        assert_eq!(
+            vec![
+                vec!["aa", "bb", "cc", "dd"],
+                vec!["aa", "cc", "dd", "ee"],
+                vec!["cc", "dd", "ee", "ff"]
+            ],
+            maximum_cliques(&network)
+        );
+        assert_eq!("aa,bb,cc,dd", part2(&network));
     }
 }
@@ -85,7 +85,7 @@ mod tests {
             MAMMMXMMMM\n\
             MXMXAXMASX\n\
         ";
-        let grid = Grid::new(10, 10, SAMPLE.lines().map(str::chars).flatten().collect()).unwrap();
+        let grid = Grid::new(10, 10, SAMPLE.lines().flat_map(str::chars).collect()).unwrap();
         let actual = part1(&grid);
         let expected = 18;
         assert_eq!(expected, actual);
@@ -105,7 +105,7 @@ mod tests {
             MAMMMXMMMM\n\
             MXMXAXMASX\n\
         ";
-        let grid = Grid::new(10, 10, SAMPLE.lines().map(str::chars).flatten().collect()).unwrap();
+        let grid = Grid::new(10, 10, SAMPLE.lines().flat_map(str::chars).collect()).unwrap();
         let actual = part2(&grid);
         let expected = 9;
         assert_eq!(expected, actual);
@@ -1,4 +1,5 @@
 use std::collections::{HashMap, HashSet};
+use std::fmt::{Display, Formatter};
 
 const INPUT: &str = "./input/day5.txt";
 
@@ -6,16 +7,110 @@ type Page = u32;
 type Rules = HashMap<Page, HashSet<Page>>;
 type Order = Vec<Page>;
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum OrderError {
+    // Pages in rule order, the last one coming before the first one again.
+    Cycle(Vec<Page>),
+    // Two pages which could come first, as no rule orders them.
+    Ambiguous(Page, Page),
+}
+
+impl Display for OrderError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OrderError::Cycle(pages) => {
+                let pages: Vec<String> = pages.iter().map(Page::to_string).collect();
+                write!(
+                    f,
+                    "The rules form a cycle: {}|{}",
+                    pages.join("|"),
+                    pages[0]
+                )
+            }
+            OrderError::Ambiguous(a, b) => write!(f, "No rule orders pages {a} and {b}"),
+        }
+    }
+}
+
+impl std::error::Error for OrderError {}
+
+fn follows(rules: &Rules, pre: Page, post: Page) -> bool {
+    rules.get(&pre).is_some_and(|rule| rule.contains(&post))
+}
+
 trait PageOrdering {
-    fn is_ordered(&self, rules: &Rules) -> bool;
+    // The first rule broken, as a pair of pages which should have been the other way around.
+    fn is_ordered(&self, rules: &Rules) -> Result<(), (Page, Page)>;
+    // Only the rules between pages of the update matter, and they must lead to a single order.
+    fn sorted(&self, rules: &Rules) -> Result<Order, OrderError>;
 }
 impl PageOrdering for Order {
-    fn is_ordered(&self, rules: &Rules) -> bool {
-        let mut seen = HashSet::new();
-        self.iter().all(|page| {
-            seen.insert(*page);
-            rules.get(page).map_or(true, |pre| pre.is_disjoint(&seen))
-        })
+    fn is_ordered(&self, rules: &Rules) -> Result<(), (Page, Page)> {
+        for (idx, &page) in self.iter().enumerate() {
+            if let Some(&earlier) = self[..idx].iter().find(|&&p| follows(rules, page, p)) {
+                return Err((page, earlier));
+            }
+        }
+        Ok(())
+    }
+
+    fn sorted(&self, rules: &Rules) -> Result<Order, OrderError> {
+        let mut indegrees: HashMap<Page, usize> = self
+            .iter()
+            .map(|&page| {
+                let count = self.iter().filter(|&&p| follows(rules, p, page)).count();
+                (page, count)
+            })
+            .collect();
+        let mut ready: Vec<Page> = self
+            .iter()
+            .copied()
+            .filter(|page| indegrees[page] == 0)
+            .collect();
+
+        let mut sorted = Vec::with_capacity(self.len());
+        while let Some(page) = ready.pop() {
+            if let Some(&other) = ready.last() {
+                return Err(OrderError::Ambiguous(other, page));
+            }
+            sorted.push(page);
+            for &next in self.iter().filter(|&&p| follows(rules, page, p)) {
+                let indegree = indegrees.get_mut(&next).unwrap();
+                *indegree -= 1;
+                if *indegree == 0 {
+                    ready.push(next);
+                }
+            }
+        }
+
+        if sorted.len() < self.len() {
+            let remaining: Vec<Page> = self
+                .iter()
+                .copied()
+                .filter(|page| indegrees[page] > 0)
+                .collect();
+            return Err(OrderError::Cycle(cycle(rules, &remaining)));
+        }
+        Ok(sorted)
+    }
+}
+
+// Every remaining page has a predecessor among the remaining ones, so walking backwards
+// eventually loops.
+fn cycle(rules: &Rules, remaining: &[Page]) -> Vec<Page> {
+    let mut path = vec![remaining[0]];
+    loop {
+        let current = *path.last().unwrap();
+        let previous = *remaining
+            .iter()
+            .find(|&&p| follows(rules, p, current))
+            .unwrap();
+        if let Some(idx) = path.iter().position(|&p| p == previous) {
+            let mut cycle = path.split_off(idx);
+            cycle.reverse();
+            return cycle;
+        }
+        path.push(previous);
     }
 }
 
@@ -46,32 +141,16 @@ fn parse_input(input: &str) -> (Rules, Vec<Order>) {
 fn part1(rules: &Rules, orders: &[Order]) -> Page {
     orders
         .iter()
-        .filter(|order| order.is_ordered(rules))
+        .filter(|order| order.is_ordered(rules).is_ok())
         .map(|order| order[order.len() / 2])
         .sum()
 }
 
-fn part2(rules: &Rules, orders: &[Order]) -> Page {
+fn part2(rules: &Rules, orders: &[Order]) -> Result<Page, OrderError> {
     orders
         .iter()
-        .filter(|order| !order.is_ordered(rules))
-        .map(|order| {
-            let mut ordered = Vec::new();
-            // For each page, find the first offending page that's out of order, and insert the
-            // new page right before it. If there isn't any, put the new page at the end.
-            for page in order {
-                if let Some(idx) = rules
-                    .get(page)
-                    .and_then(|rule| ordered.iter().position(|p| rule.contains(p)))
-                {
-                    ordered.insert(idx, *page);
-                } else {
-                    ordered.push(*page);
-                }
-            }
-
-            ordered[ordered.len() / 2]
-        })
+        .filter(|order| order.is_ordered(rules).is_err())
+        .map(|order| order.sorted(rules).map(|sorted| sorted[sorted.len() / 2]))
         .sum()
 }
 
@@ -80,7 +159,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let (rules, orders) = parse_input(&input);
 
     println!("The first answer is: {}", part1(&rules, &orders));
-    println!("The second answer is: {}", part2(&rules, &orders));
+    println!("The second answer is: {}", part2(&rules, &orders)?);
 
     Ok(())
 }
@@ -132,7 +211,53 @@ mod tests {
     fn test_part2() {
         let (rules, orders) = parse_input(SAMPLE);
         let actual = part2(&rules, &orders);
-        let expected = 123;
+        let expected = Ok(123);
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn violations() {
+        let (rules, orders) = parse_input(SAMPLE);
+        let actual: Vec<_> = orders
+            .iter()
+            .map(|order| order.is_ordered(&rules))
+            .collect();
+        let expected = vec![
+            Ok(()),
+            Ok(()),
+            Ok(()),
+            Err((97, 75)),
+            Err((29, 13)),
+            Err((75, 13)),
+        ];
+        assert_eq!(expected, actual);
+
+        let actual: Vec<_> = orders.iter().map(|order| order.sorted(&rules)).collect();
+        let expected = vec![
+            Ok(vec![75, 47, 61, 53, 29]),
+            Ok(vec![97, 61, 53, 29, 13]),
+            Ok(vec![75, 29, 13]),
+            Ok(vec![97, 75, 47, 61, 53]),
+            Ok(vec![61, 29, 13]),
+            Ok(vec![97, 75, 47, 29, 13]),
+        ];
         assert_eq!(expected, actual);
     }
+
+    #[test]
+    fn unsortable() {
+        let (rules, orders) = parse_input("1|2\n2|3\n3|1\n3|4\n\n4,3,2,1\n1,2,5\n2,1,5\n");
+
+        let actual = orders[0].sorted(&rules);
+        assert_eq!(Err(OrderError::Cycle(vec![1, 2, 3])), actual);
+        assert_eq!(
+            "The rules form a cycle: 1|2|3|1",
+            actual.unwrap_err().to_string()
+        );
+        assert_eq!(Err(OrderError::Ambiguous(1, 5)), orders[1].sorted(&rules));
+        assert_eq!(
+            Err(OrderError::Ambiguous(1, 5)),
+            part2(&rules, &orders[1..])
+        );
+    }
 }
@@ -1,4 +1,5 @@
 use radixal::IntoDigits;
+use std::fmt::{Display, Formatter};
 
 const INPUT: &str = "./input/day7.txt";
 
@@ -21,72 +22,155 @@ fn parse_input(input: &str) -> Vec<Equation> {
         .collect()
 }
 
-fn part1(equations: &[Equation]) -> Value {
-    equations
-        .iter()
-        .filter_map(|(total, values)| {
-            let mut possibilities = vec![*total];
-
-            // We'll iterate backwards, eliminating negative values and divisions with a remainder.
-            // Skip the first one, as we'll want to check its presence.
-            for &value in values.iter().skip(1).rev() {
-                let mut new_possibilities = Vec::new();
-                for p in possibilities {
-                    if let Some(sub) = p.checked_sub(value) {
-                        new_possibilities.push(sub);
-                    }
-                    if p % value == 0 {
-                        new_possibilities.push(p / value)
-                    }
-                }
-                possibilities = new_possibilities;
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Operator {
+    Add,
+    Multiply,
+    Concatenate,
+}
+
+impl Operator {
+    // What the left operand must have been for the operator to give this result, if anything.
+    fn undo(self, result: Value, value: Value) -> Option<Value> {
+        match self {
+            Operator::Add => result.checked_sub(value),
+            Operator::Multiply => result
+                .checked_div(value)
+                .filter(|_| result.is_multiple_of(value)),
+            Operator::Concatenate => {
+                let separator = 10_u64.checked_pow(value.nbr_decimal_digits() as u32)?;
+                Some(result / separator).filter(|_| result % separator == value)
             }
-            if possibilities.contains(&values[0]) {
-                Some(total)
-            } else {
-                None
+        }
+    }
+}
+
+impl Display for Operator {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Operator::Add => write!(f, "+"),
+            Operator::Multiply => write!(f, "*"),
+            Operator::Concatenate => write!(f, "||"),
+        }
+    }
+}
+
+const PART1: &[Operator] = &[Operator::Add, Operator::Multiply];
+const PART2: &[Operator] = &[Operator::Add, Operator::Multiply, Operator::Concatenate];
+
+// We'll iterate backwards, eliminating negative values and divisions with a remainder, as
+// operators are evaluated left to right. `found` is given each solution's operators in order, and
+// tells whether to keep searching.
+fn search(
+    (total, values): &Equation,
+    operators: &[Operator],
+    found: &mut impl FnMut(&[Operator]) -> bool,
+) {
+    fn recursive(
+        target: Value,
+        values: &[Value],
+        operators: &[Operator],
+        chosen: &mut Vec<Operator>,
+        found: &mut impl FnMut(&[Operator]) -> bool,
+    ) -> bool {
+        match values {
+            [] => true,
+            [first] if *first == target => {
+                let solution: Vec<Operator> = chosen.iter().rev().copied().collect();
+                found(&solution)
             }
-        })
-        .sum()
+            [_] => true,
+            [rest @ .., last] => operators.iter().all(|&operator| {
+                let Some(target) = operator.undo(target, *last) else {
+                    return true;
+                };
+                chosen.push(operator);
+                let keep_searching = recursive(target, rest, operators, chosen, found);
+                chosen.pop();
+                keep_searching
+            }),
+        }
+    }
+
+    recursive(*total, values, operators, &mut Vec::new(), found);
 }
 
-fn part2(equations: &[Equation]) -> Value {
+fn solve(equation: &Equation, operators: &[Operator]) -> Option<Vec<Operator>> {
+    let mut solution = None;
+    search(equation, operators, &mut |found| {
+        solution = Some(found.to_vec());
+        false
+    });
+    solution
+}
+
+fn solutions(equation: &Equation, operators: &[Operator]) -> Vec<Vec<Operator>> {
+    let mut solutions = Vec::new();
+    search(equation, operators, &mut |found| {
+        solutions.push(found.to_vec());
+        true
+    });
+    solutions
+}
+
+fn count_solutions(equation: &Equation, operators: &[Operator]) -> usize {
+    let mut count = 0;
+    search(equation, operators, &mut |_| {
+        count += 1;
+        true
+    });
+    count
+}
+
+fn render((total, values): &Equation, solution: &[Operator]) -> String {
+    let mut rendered = format!("{total} = {}", values[0]);
+    for (operator, value) in solution.iter().zip(&values[1..]) {
+        rendered.push_str(&format!(" {operator} {value}"));
+    }
+    rendered
+}
+
+fn calibration(equations: &[Equation], operators: &[Operator]) -> Value {
     equations
         .iter()
-        .filter_map(|(total, values)| {
-            let mut possibilities = vec![*total];
-
-            // We'll iterate backwards, eliminating negative values and divisions with a remainder.
-            // Skip the first one, as we'll want to check its presence.
-            for &value in values.iter().skip(1).rev() {
-                let mut new_possibilities = Vec::new();
-                for p in possibilities {
-                    if let Some(sub) = p.checked_sub(value) {
-                        new_possibilities.push(sub);
-                    }
-                    if p % value == 0 {
-                        new_possibilities.push(p / value)
-                    }
-                    let separator = 10_u64.pow(value.nbr_decimal_digits() as u32);
-                    if p % separator == value {
-                        new_possibilities.push(p / separator);
-                    }
-                }
-                possibilities = new_possibilities;
-            }
-            if possibilities.contains(&values[0]) {
-                Some(total)
-            } else {
-                None
-            }
-        })
+        .filter(|equation| solve(equation, operators).is_some())
+        .map(|(total, _)| total)
         .sum()
 }
 
+fn part1(equations: &[Equation]) -> Value {
+    calibration(equations, PART1)
+}
+
+fn part2(equations: &[Equation]) -> Value {
+    calibration(equations, PART2)
+}
+
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let input = std::fs::read_to_string(INPUT)?;
     let equations = parse_input(&input);
 
+    match std::env::args().nth(1).as_deref() {
+        None => (),
+        Some("--explain") => {
+            for equation in &equations {
+                if let Some(solution) = solve(equation, PART2) {
+                    let count = count_solutions(equation, PART2);
+                    let plural = if count == 1 { "" } else { "s" };
+                    println!("{} ({count} solution{plural})", render(equation, &solution));
+                }
+            }
+        }
+        Some("--all") => {
+            for equation in &equations {
+                for solution in solutions(equation, PART2) {
+                    println!("{}", render(equation, &solution));
+                }
+            }
+        }
+        Some(_) => return Err("Usage: day7 [--explain | --all]".into()),
+    }
+
     println!("The first answer is: {}", part1(&equations));
     println!("The second answer is: {}", part2(&equations));
 
@@ -111,15 +195,55 @@ mod test {
 
     #[test]
     fn test_part1() {
-        let actual = part1(&parse_input(&SAMPLE));
+        let actual = part1(&parse_input(SAMPLE));
         let expected = 3749;
         assert_eq!(expected, actual);
     }
 
     #[test]
     fn test_part2() {
-        let actual = part2(&parse_input(&SAMPLE));
+        let actual = part2(&parse_input(SAMPLE));
         let expected = 11387;
         assert_eq!(expected, actual);
     }
+
+    #[test]
+    fn rendered_solutions() {
+        let equations = parse_input(SAMPLE);
+
+        let solution = solve(&equations[8], PART1).unwrap();
+        assert_eq!("292 = 11 + 6 * 16 + 20", render(&equations[8], &solution));
+        let solution = solve(&equations[4], PART2).unwrap();
+        assert_eq!("7290 = 6 * 8 || 6 * 15", render(&equations[4], &solution));
+        assert_eq!(None, solve(&equations[2], PART2));
+    }
+
+    #[test]
+    fn all_solutions() {
+        let equations = parse_input(SAMPLE);
+
+        let mut actual = solutions(&equations[1], PART1);
+        actual.sort_by_key(|solution| solution.iter().map(ToString::to_string).collect::<String>());
+        let expected = vec![
+            vec![Operator::Multiply, Operator::Add],
+            vec![Operator::Add, Operator::Multiply],
+        ];
+        assert_eq!(expected, actual);
+
+        let counts: Vec<usize> = equations
+            .iter()
+            .map(|equation| count_solutions(equation, PART2))
+            .collect();
+        assert_eq!(vec![1, 2, 0, 1, 1, 0, 1, 0, 1], counts);
+    }
+
+    #[test]
+    fn custom_operators() {
+        let equations = parse_input(SAMPLE);
+
+        let solution = solve(&equations[3], &[Operator::Concatenate]).unwrap();
+        assert_eq!("156 = 15 || 6", render(&equations[3], &solution));
+        assert_eq!(None, solve(&equations[3], &[Operator::Add]));
+        assert_eq!(190, calibration(&equations, &[Operator::Multiply]));
+    }
 }
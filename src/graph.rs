@@ -0,0 +1,498 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use std::hash::{Hash, Hasher};
+
+// Rows may end with zero words (after removals, or from set operations), which equality and
+// hashing ignore.
+#[derive(Debug, Default, Clone)]
+pub struct BitSet(Vec<u64>);
+
+impl BitSet {
+    fn words(&self) -> &[u64] {
+        let len = self
+            .0
+            .iter()
+            .rposition(|&w| w != 0)
+            .map_or(0, |idx| idx + 1);
+        &self.0[..len]
+    }
+
+    pub fn full(len: usize) -> Self {
+        (0..len).collect()
+    }
+
+    pub fn insert(&mut self, value: usize) -> bool {
+        let (word, bit) = (value / 64, 1 << (value % 64));
+        if word >= self.0.len() {
+            self.0.resize(word + 1, 0);
+        }
+        let inserted = self.0[word] & bit == 0;
+        self.0[word] |= bit;
+        inserted
+    }
+
+    pub fn remove(&mut self, value: usize) -> bool {
+        let (word, bit) = (value / 64, 1 << (value % 64));
+        match self.0.get_mut(word) {
+            Some(w) if *w & bit != 0 => {
+                *w &= !bit;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn contains(&self, value: usize) -> bool {
+        self.0
+            .get(value / 64)
+            .is_some_and(|w| w & (1 << (value % 64)) != 0)
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.iter().map(|w| w.count_ones() as usize).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.iter().all(|&w| w == 0)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        self.0.iter().enumerate().flat_map(|(idx, &word)| {
+            std::iter::successors(Some(word).filter(|&w| w != 0), |&w| {
+                Some(w & (w - 1)).filter(|&w| w != 0)
+            })
+            .map(move |w| idx * 64 + w.trailing_zeros() as usize)
+        })
+    }
+
+    pub fn intersection(&self, other: &Self) -> Self {
+        Self(self.0.iter().zip(&other.0).map(|(a, b)| a & b).collect())
+    }
+
+    pub fn intersection_len(&self, other: &Self) -> usize {
+        self.0
+            .iter()
+            .zip(&other.0)
+            .map(|(a, b)| (a & b).count_ones() as usize)
+            .sum()
+    }
+
+    pub fn union(&self, other: &Self) -> Self {
+        let (long, short) = if self.0.len() >= other.0.len() {
+            (self, other)
+        } else {
+            (other, self)
+        };
+        let mut union = long.clone();
+        union.0.iter_mut().zip(&short.0).for_each(|(a, b)| *a |= b);
+        union
+    }
+
+    pub fn difference(&self, other: &Self) -> Self {
+        let mut difference = self.clone();
+        difference
+            .0
+            .iter_mut()
+            .zip(&other.0)
+            .for_each(|(a, b)| *a &= !b);
+        difference
+    }
+}
+
+impl PartialEq for BitSet {
+    fn eq(&self, other: &Self) -> bool {
+        self.words() == other.words()
+    }
+}
+
+impl Eq for BitSet {}
+
+impl Hash for BitSet {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.words().hash(state);
+    }
+}
+
+impl FromIterator<usize> for BitSet {
+    fn from_iter<I: IntoIterator<Item = usize>>(iter: I) -> Self {
+        let mut set = Self::default();
+        for value in iter {
+            set.insert(value);
+        }
+        set
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DegreeStats {
+    pub min: usize,
+    pub max: usize,
+    pub mean: f64,
+    // Number of nodes for each degree.
+    pub histogram: BTreeMap<usize, usize>,
+}
+
+// An undirected graph, without self-loops. Nodes are interned names, and the neighbours of each
+// node are a row of bits, which stays compact for dense graphs.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Graph {
+    names: Vec<String>,
+    ids: HashMap<String, usize>,
+    rows: Vec<BitSet>,
+}
+
+impl Graph {
+    pub fn from_edges<'a, I: IntoIterator<Item = (&'a str, &'a str)>>(edges: I) -> Self {
+        let mut graph = Self::default();
+        for (a, b) in edges {
+            graph.add_edge(a, b);
+        }
+        graph
+    }
+
+    pub fn add_node(&mut self, name: &str) -> usize {
+        if let Some(&id) = self.ids.get(name) {
+            return id;
+        }
+
+        let id = self.names.len();
+        self.names.push(name.to_string());
+        self.ids.insert(name.to_string(), id);
+        self.rows.push(BitSet::default());
+        id
+    }
+
+    pub fn add_edge(&mut self, a: &str, b: &str) -> (usize, usize) {
+        let a = self.add_node(a);
+        let b = self.add_node(b);
+        if a != b {
+            self.rows[a].insert(b);
+            self.rows[b].insert(a);
+        }
+        (a, b)
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    pub fn id(&self, name: &str) -> Option<usize> {
+        self.ids.get(name).copied()
+    }
+
+    pub fn name(&self, id: usize) -> &str {
+        &self.names[id]
+    }
+
+    pub fn neighbours(&self, id: usize) -> &BitSet {
+        &self.rows[id]
+    }
+
+    pub fn has_edge(&self, a: usize, b: usize) -> bool {
+        self.rows[a].contains(b)
+    }
+
+    pub fn degree(&self, id: usize) -> usize {
+        self.rows[id].len()
+    }
+
+    pub fn edge_count(&self) -> usize {
+        (0..self.len()).map(|id| self.degree(id)).sum::<usize>() / 2
+    }
+
+    pub fn degree_stats(&self) -> Option<DegreeStats> {
+        let mut histogram = BTreeMap::new();
+        for id in 0..self.len() {
+            *histogram.entry(self.degree(id)).or_insert(0) += 1;
+        }
+
+        Some(DegreeStats {
+            min: *histogram.keys().next()?,
+            max: *histogram.keys().next_back()?,
+            mean: 2.0 * self.edge_count() as f64 / self.len() as f64,
+            histogram,
+        })
+    }
+
+    // Each triangle is listed once, with its nodes in increasing order.
+    pub fn triangles(&self) -> Vec<[usize; 3]> {
+        let mut triangles = Vec::new();
+        for a in 0..self.len() {
+            for b in self.rows[a].iter().filter(|&b| b > a) {
+                let common = self.rows[a].intersection(&self.rows[b]);
+                triangles.extend(common.iter().filter(|&c| c > b).map(|c| [a, b, c]));
+            }
+        }
+        triangles
+    }
+
+    // Cliques are only ever extended with nodes larger than their last one, so each is counted
+    // once.
+    pub fn count_cliques(&self, k: usize) -> usize {
+        fn recursive(graph: &Graph, candidates: &BitSet, remaining: usize) -> usize {
+            if remaining == 0 {
+                return 1;
+            }
+            candidates
+                .iter()
+                .map(|v| {
+                    let candidates: BitSet = graph.rows[v]
+                        .intersection(candidates)
+                        .iter()
+                        .filter(|&u| u > v)
+                        .collect();
+                    recursive(graph, &candidates, remaining - 1)
+                })
+                .sum()
+        }
+
+        recursive(self, &BitSet::full(self.len()), k)
+    }
+
+    // Bron–Kerbosch with pivoting, only keeping the largest cliques. Branches that cannot grow as
+    // large as the best clique so far are cut short. Each clique is sorted by id.
+    pub fn maximum_cliques(&self) -> Vec<Vec<usize>> {
+        fn recursive(
+            graph: &Graph,
+            clique: &mut Vec<usize>,
+            mut candidates: BitSet,
+            mut excluded: BitSet,
+            cliques: &mut Vec<Vec<usize>>,
+        ) {
+            let best = cliques.first().map_or(0, Vec::len);
+            if clique.len() + candidates.len() < best {
+                return;
+            }
+            if candidates.is_empty() && excluded.is_empty() {
+                if clique.len() > best {
+                    cliques.clear();
+                }
+                let mut clique = clique.clone();
+                clique.sort_unstable();
+                cliques.push(clique);
+                return;
+            }
+
+            // Any maximal clique contains either the pivot or one of its non-neighbours.
+            let pivot = candidates
+                .union(&excluded)
+                .iter()
+                .max_by_key(|&u| graph.rows[u].intersection_len(&candidates))
+                .unwrap();
+            for v in candidates.difference(&graph.rows[pivot]).iter() {
+                let neighbours = &graph.rows[v];
+                clique.push(v);
+                recursive(
+                    graph,
+                    clique,
+                    candidates.intersection(neighbours),
+                    excluded.intersection(neighbours),
+                    cliques,
+                );
+                clique.pop();
+                candidates.remove(v);
+                excluded.insert(v);
+            }
+        }
+
+        let mut cliques = Vec::new();
+        if !self.is_empty() {
+            recursive(
+                self,
+                &mut Vec::new(),
+                BitSet::full(self.len()),
+                BitSet::default(),
+                &mut cliques,
+            );
+        }
+        cliques.sort_unstable();
+        cliques
+    }
+
+    // Graphviz format, with isolated nodes listed on their own.
+    pub fn to_dot(&self) -> String {
+        let quote = |id: usize| format!("\"{}\"", self.names[id].replace('"', "\\\""));
+        let mut dot = String::from("graph {\n");
+        for a in 0..self.len() {
+            if self.rows[a].is_empty() {
+                writeln!(dot, "    {};", quote(a)).unwrap();
+            }
+            for b in self.rows[a].iter().filter(|&b| b > a) {
+                writeln!(dot, "    {} -- {};", quote(a), quote(b)).unwrap();
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // Two triangles sharing an edge, a pendant node, and an isolated one.
+    fn graph() -> Graph {
+        let mut graph = Graph::from_edges([
+            ("a", "b"),
+            ("b", "c"),
+            ("a", "c"),
+            ("c", "d"),
+            ("b", "d"),
+            ("d", "e"),
+        ]);
+        graph.add_node("f");
+        graph
+    }
+
+    #[test]
+    fn bitset() {
+        let mut set: BitSet = [1, 64, 130].into_iter().collect();
+        assert!(set.contains(64));
+        assert!(!set.contains(65));
+        assert!(!set.insert(130));
+        assert!(set.remove(1));
+        assert!(!set.remove(1000));
+        assert_eq!(vec![64, 130], set.iter().collect::<Vec<_>>());
+
+        let other = BitSet::full(100);
+        assert_eq!(
+            vec![64],
+            set.intersection(&other).iter().collect::<Vec<_>>()
+        );
+        assert_eq!(1, set.intersection_len(&other));
+        assert_eq!(101, set.union(&other).len());
+        assert_eq!(vec![130], set.difference(&other).iter().collect::<Vec<_>>());
+        assert!(set.difference(&set).is_empty());
+    }
+
+    #[test]
+    fn bitset_equality() {
+        let hash = |set: &BitSet| {
+            let mut hasher = std::collections::hash_map::DefaultHasher::new();
+            set.hash(&mut hasher);
+            hasher.finish()
+        };
+
+        let single: BitSet = [1].into_iter().collect();
+        let mut removed: BitSet = [1, 200].into_iter().collect();
+        removed.remove(200);
+        assert_eq!(single, removed);
+        assert_eq!(hash(&single), hash(&removed));
+
+        let long = BitSet::full(300);
+        assert_eq!(single, single.intersection(&long).intersection(&removed));
+        assert_eq!(long, long.union(&single));
+        assert_eq!(BitSet::default(), single.difference(&removed));
+        assert_eq!(hash(&BitSet::default()), hash(&single.difference(&removed)));
+        assert_ne!(single, long);
+    }
+
+    #[test]
+    fn interning() {
+        let graph = graph();
+        assert_eq!(6, graph.len());
+        assert_eq!(6, graph.edge_count());
+        assert_eq!(Some(3), graph.id("d"));
+        assert_eq!("e", graph.name(4));
+        assert!(graph.has_edge(1, 3));
+        assert!(!graph.has_edge(0, 3));
+        assert_eq!(None, graph.id("g"));
+    }
+
+    #[test]
+    fn degree_stats() {
+        let stats = graph().degree_stats().unwrap();
+        assert_eq!(0, stats.min);
+        assert_eq!(3, stats.max);
+        assert_eq!(2.0, stats.mean);
+        assert_eq!(
+            BTreeMap::from([(0, 1), (1, 1), (2, 1), (3, 3)]),
+            stats.histogram
+        );
+        assert_eq!(None, Graph::default().degree_stats());
+    }
+
+    #[test]
+    fn cliques() {
+        let graph = graph();
+        assert_eq!(vec![[0, 1, 2], [1, 2, 3]], graph.triangles());
+        assert_eq!(1, graph.count_cliques(0));
+        assert_eq!(6, graph.count_cliques(1));
+        assert_eq!(6, graph.count_cliques(2));
+        assert_eq!(2, graph.count_cliques(3));
+        assert_eq!(0, graph.count_cliques(4));
+        assert_eq!(vec![vec![0, 1, 2], vec![1, 2, 3]], graph.maximum_cliques());
+
+        let complete = Graph::from_edges([
+            ("a", "b"),
+            ("a", "c"),
+            ("a", "d"),
+            ("b", "c"),
+            ("b", "d"),
+            ("c", "d"),
+        ]);
+        assert_eq!(4, complete.count_cliques(3));
+        assert_eq!(1, complete.count_cliques(4));
+        assert_eq!(vec![vec![0, 1, 2, 3]], complete.maximum_cliques());
+    }
+
+    #[test]
+    fn dot() {
+        let graph = Graph::from_edges([("a", "b"), ("b", "c\"d")]);
+        let expected = "graph {\n    \"a\" -- \"b\";\n    \"b\" -- \"c\\\"d\";\n}\n";
+        assert_eq!(expected, graph.to_dot());
+
+        let mut graph = graph;
+        graph.add_node("e");
+        assert!(graph.to_dot().ends_with("    \"e\";\n}\n"));
+    }
+
+    #[test]
+    fn maximum_cliques_brute_force() {
+        let mut seed: u64 = 12345;
+        let mut random = move |modulo: u64| {
+            seed = seed
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (seed >> 33) % modulo
+        };
+
+        for _ in 0..50 {
+            let nodes = 1 + random(12) as usize;
+            let density = 1 + random(9);
+            let mut graph = Graph::default();
+            for a in 0..nodes {
+                graph.add_node(&a.to_string());
+                for b in 0..a {
+                    if random(10) < density {
+                        graph.add_edge(&a.to_string(), &b.to_string());
+                    }
+                }
+            }
+
+            // Every subset of nodes, as a bitmask, keeping the largest cliques.
+            let mut expected: Vec<Vec<usize>> = Vec::new();
+            for mask in 1_usize..1 << nodes {
+                let clique: Vec<usize> = (0..nodes).filter(|&n| mask >> n & 1 == 1).collect();
+                let is_clique = clique
+                    .iter()
+                    .enumerate()
+                    .all(|(idx, &a)| clique[idx + 1..].iter().all(|&b| graph.has_edge(a, b)));
+                let best = expected.first().map_or(0, Vec::len);
+                if is_clique && clique.len() >= best {
+                    if clique.len() > best {
+                        expected.clear();
+                    }
+                    expected.push(clique);
+                }
+            }
+            expected.sort_unstable();
+
+            assert_eq!(expected, graph.maximum_cliques());
+        }
+    }
+}
@@ -124,6 +124,75 @@ impl<T> Grid<T> {
                 .map(move |(idx, value)| (Position::new(idx, idy), value))
         })
     }
+
+    // Label connected groups of cells, where neighbouring cells belong together if `connected`
+    // holds for their values. Returns the label of each cell, along with the cells of each
+    // component (in order of their first cell, row by row).
+    pub fn components_by<F>(
+        &self,
+        connectivity: Connectivity,
+        connected: F,
+    ) -> (Grid<usize>, Vec<Vec<Position>>)
+    where
+        F: Fn(&T, &T) -> bool,
+    {
+        let mut labels: Vec<Option<usize>> = vec![None; self.grid.len()];
+        let mut components = Vec::new();
+
+        for (start, _) in self.iter() {
+            if labels[start.x + start.y * self.width].is_some() {
+                continue;
+            }
+
+            let label = components.len();
+            let mut component = Vec::new();
+            let mut to_visit = vec![start];
+            labels[start.x + start.y * self.width] = Some(label);
+
+            while let Some(position) = to_visit.pop() {
+                component.push(position);
+                let value = self.get(position).unwrap();
+                let neighbours: Vec<Position> = match connectivity {
+                    Connectivity::Four => position.neighbours().collect(),
+                    Connectivity::Eight => position.extended_neighbours().collect(),
+                };
+
+                for neighbour in neighbours {
+                    if let Some(other) = self.get(neighbour) {
+                        let idx = neighbour.x + neighbour.y * self.width;
+                        if labels[idx].is_none() && connected(value, other) {
+                            labels[idx] = Some(label);
+                            to_visit.push(neighbour);
+                        }
+                    }
+                }
+            }
+
+            component.sort_unstable();
+            components.push(component);
+        }
+
+        let labels = labels.into_iter().map(Option::unwrap).collect();
+        (
+            Grid::new(self.height, self.width, labels).unwrap(),
+            components,
+        )
+    }
+
+    pub fn components(&self, connectivity: Connectivity) -> (Grid<usize>, Vec<Vec<Position>>)
+    where
+        T: PartialEq,
+    {
+        self.components_by(connectivity, |a, b| a == b)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Connectivity {
+    // Orthogonal neighbours only.
+    Four,
+    // Diagonal neighbours as well.
+    Eight,
 }
 
 #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -167,18 +236,7 @@ impl Position {
     }
 
     pub fn manhattan_distance(self, other: Self) -> usize {
-        let dx = if self.x > other.x {
-            self.x - other.x
-        } else {
-            other.x - self.x
-        };
-        let dy = if self.y > other.y {
-            self.y - other.y
-        } else {
-            other.y - self.y
-        };
-
-        dx + dy
+        self.x.abs_diff(other.x) + self.y.abs_diff(other.y)
     }
 
     pub fn line(self, direction: Direction) -> impl std::iter::Iterator<Item = Position> {
@@ -275,3 +333,31 @@ impl Direction {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn components() {
+        let grid = Grid::new(3, 4, "ab.bbaabaabb".chars().collect()).unwrap();
+
+        let (labels, components) = grid.components(Connectivity::Four);
+        let expected = vec![0, 1, 2, 3, 4, 5, 5, 3, 5, 5, 3, 3];
+        assert_eq!(expected, labels.iter().map(|(_, &l)| l).collect::<Vec<_>>());
+        assert_eq!(6, components.len());
+        assert_eq!(
+            vec![
+                Position::new(1, 1),
+                Position::new(2, 1),
+                Position::new(0, 2),
+                Position::new(1, 2)
+            ],
+            components[5]
+        );
+
+        let (_, components) = grid.components(Connectivity::Eight);
+        let sizes: Vec<usize> = components.iter().map(Vec::len).collect();
+        assert_eq!(vec![5, 2, 1, 4], sizes);
+    }
+}